@@ -3,7 +3,7 @@ use crate::vec3::*;
 use crate::ray::Ray;
 
 #[derive(Copy, Clone)]
-struct AABB {
+pub struct AABB {
     x: Interval,
     y: Interval,
     z: Interval,
@@ -26,7 +26,35 @@ impl AABB {
         }
     }
 
-    pub fn axis(&self, n: i32) -> Interval {
+    pub fn aabb_boxes(box0: &AABB, box1: &AABB) -> AABB {
+        Self {
+            x: Interval::interval_union(&box0.x, &box1.x),
+            y: Interval::interval_union(&box0.y, &box1.y),
+            z: Interval::interval_union(&box0.z, &box1.z),
+        }
+    }
+
+    // adjust the box so that no side is narrower than some delta, padding if necessary,
+    // planar shapes would otherwise get a box of zero thickness
+    pub fn pad_to_minimums(&self) -> AABB {
+        let delta = 0.0001;
+        let pad = |i: Interval| if i.size() < delta { i.expand(delta) } else { i };
+        Self {
+            x: pad(self.x),
+            y: pad(self.y),
+            z: pad(self.z),
+        }
+    }
+
+    pub fn universe() -> AABB {
+        Self {
+            x: Interval::universe(),
+            y: Interval::universe(),
+            z: Interval::universe(),
+        }
+    }
+
+    pub fn axis(&self, n: usize) -> Interval {
         if n == 1 {
             return self.y;
         } 
@@ -36,16 +64,43 @@ impl AABB {
         return self.x;
     }
 
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() { 0 } else { 2 }
+        } else {
+            if self.y.size() > self.z.size() { 1 } else { 2 }
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        Vec3::vec3(
+            0.5 * (self.x.min() + self.x.max()),
+            0.5 * (self.y.min() + self.y.max()),
+            0.5 * (self.z.min() + self.z.max()),
+        )
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let dx = self.x.size();
+        let dy = self.y.size();
+        let dz = self.z.size();
+        return 2.0 * (dx * dy + dy * dz + dz * dx);
+    }
+
     pub fn hit(&self, r: &Ray, ray_t: Interval) -> bool {
+        let mut t_min = ray_t.min();
+        let mut t_max = ray_t.max();
+        let origin = r.origin();
+        let direction = r.direction();
+
         for a in 0..3 {
-            let t0 = f64::min((self.axis(a).min() - r.origin()[a]) / r.direction()[a],
-                              (self.axis(a).max() - r.origin()[a]) / r.direction()[a]);
-            let t1 = f64::max((self.axis(a).min() - r.origin()[a]) / r.direction()[a],
-                              (self.axis(a).max() - r.origin()[a]) / r.direction()[a]);
-
-            ray_t.min() = f64::max(t0, ray_t.min());
-            ray_t.max() = f64::min(t1, ray_t.max());
-            if ray_t.max() <= ray_t.min() {
+            let inv_d = 1.0 / direction[a];
+            let t0 = (self.axis(a).min() - origin[a]) * inv_d;
+            let t1 = (self.axis(a).max() - origin[a]) * inv_d;
+
+            t_min = f64::max(f64::min(t0, t1), t_min);
+            t_max = f64::min(f64::max(t0, t1), t_max);
+            if t_max <= t_min {
                 return false;
             }
         }
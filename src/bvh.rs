@@ -0,0 +1,89 @@
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::aabb::AABB;
+
+pub struct BvhNode {
+    left: Option<Box<dyn Hittable>>,
+    right: Option<Box<dyn Hittable>>,
+    bbox: AABB,
+}
+
+impl BvhNode {
+    pub fn bvh_node(list: HittableList) -> BvhNode {
+        BvhNode::from_objects(list.into_objects())
+    }
+
+    pub fn from_objects(mut objects: Vec<Box<dyn Hittable>>) -> BvhNode {
+        // build the bounding box of the span of source objects
+        let mut bbox = AABB::identity();
+        for object in objects.iter() {
+            bbox = AABB::aabb_boxes(&bbox, &object.bounding_box());
+        }
+
+        if objects.len() <= 1 {
+            return BvhNode {
+                left: objects.pop(),
+                right: None,
+                bbox,
+            };
+        }
+
+        if objects.len() == 2 {
+            let right = objects.pop();
+            let left = objects.pop();
+            return BvhNode {
+                left,
+                right,
+                bbox,
+            };
+        }
+
+        // split the objects in half along the longest axis of the span
+        let axis = bbox.longest_axis();
+        objects.sort_by(|a, b| {
+            let a_min = a.bounding_box().axis(axis).min();
+            let b_min = b.bounding_box().axis(axis).min();
+            a_min.total_cmp(&b_min)
+        });
+
+        let mid = objects.len() / 2;
+        let upper = objects.split_off(mid);
+        BvhNode {
+            left: Some(Box::new(BvhNode::from_objects(objects))),
+            right: Some(Box::new(BvhNode::from_objects(upper))),
+            bbox,
+        }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        if !self.bbox.hit(r, ray_t) {
+            return false;
+        }
+
+        let mut closest_so_far = ray_t.max();
+        let mut hit_anything = false;
+
+        if let Some(left) = &self.left {
+            if left.hit(r, ray_t, rec) {
+                hit_anything = true;
+                closest_so_far = rec.t();
+            }
+        }
+
+        if let Some(right) = &self.right {
+            if right.hit(r, Interval::interval(ray_t.min(), closest_so_far), rec) {
+                hit_anything = true;
+            }
+        }
+
+        return hit_anything;
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
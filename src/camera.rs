@@ -1,13 +1,24 @@
-use crate::color::write_color;
 use crate::hittable::*;
+use crate::hittable_list::HittableList;
+use crate::pdf::*;
 use crate::ray::Ray;
 use crate::vec3::*;
 use crate::interval::Interval;
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use crate::utility::random_double;
+use crate::framebuffer::Framebuffer;
+use crate::color::{gamma_to_linear, luminance};
+use crate::utility::{mix_seed, random_double, Rng};
 use crate::material::*;
-use async_std::task::*;
+use crate::sampler::{PixelSampler, SamplerKind};
+use crate::spectrum::{at_wavelength, sample_wavelength, wavelength_to_rgb};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+#[derive(Copy, Clone, Debug)]
+pub enum Background {
+    // the white to blue gradient from the book
+    Sky,
+    Color(Vec3),
+}
 
 pub struct Camera {
    aspect_ratio: f64,
@@ -30,6 +41,17 @@ pub struct Camera {
    focus_dist: f64,
    defocus_disk_u: Vec3,
    defocus_disk_v: Vec3,
+   parallel: bool,
+   seed: Option<u64>,
+   background: Background,
+   sampler: SamplerKind,
+   spectral: bool,
+   roulette_depth: i32,
+   noise_threshold: f64,
+   min_samples: i32,
+   sample_counts: Vec<i32>,
+   // the image width the counts were taken at, the width may have changed since
+   sample_counts_width: usize,
 }
 
 impl Camera {
@@ -55,6 +77,16 @@ impl Camera {
             focus_dist: 0.0,
             defocus_disk_u: Vec3::identity(),
             defocus_disk_v: Vec3::identity(),
+            parallel: true,
+            seed: None,
+            background: Background::Sky,
+            sampler: SamplerKind::Sobol,
+            spectral: false,
+            roulette_depth: 3,
+            noise_threshold: 0.0,
+            min_samples: 16,
+            sample_counts: Vec::new(),
+            sample_counts_width: 0,
         }
     }
 
@@ -70,10 +102,16 @@ impl Camera {
         self.samples_per_pixel = *samples;
     }
 
+    // max_depth stays a hard limit on the number of bounces
     pub fn set_max_depth(&mut self, depth: &i32) {
         self.max_depth = *depth;
     }
 
+    // bounces every path takes before russian roulette can end it
+    pub fn set_roulette_depth(&mut self, depth: &i32) {
+        self.roulette_depth = *depth;
+    }
+
     pub fn set_fov(&mut self, fov: &f64) {
         self.v_fov = *fov;
     }
@@ -98,37 +136,132 @@ impl Camera {
         self.focus_dist = *dist;
     }
 
-    pub fn render(&mut self, world: &dyn Hittable) -> std::io::Result<()> {
+    pub fn set_parallel(&mut self, parallel: &bool) {
+        self.parallel = *parallel;
+    }
+
+    pub fn set_seed(&mut self, seed: &u64) {
+        self.seed = Some(*seed);
+    }
+
+    pub fn set_background(&mut self, background: &Background) {
+        self.background = *background;
+    }
+
+    pub fn set_sampler(&mut self, sampler: &SamplerKind) {
+        self.sampler = *sampler;
+    }
+
+    // every path carries a single wavelength, colors are converted to spectra along it and
+    // dispersive glass bends each wavelength by its own index
+    pub fn set_spectral(&mut self, spectral: &bool) {
+        self.spectral = *spectral;
+    }
+
+    // above zero, pixels stop sampling once the standard error of their brightness falls
+    // below this fraction of it, samples_per_pixel becomes the most any pixel takes
+    pub fn set_noise_threshold(&mut self, threshold: &f64) {
+        self.noise_threshold = *threshold;
+    }
+
+    // samples every pixel takes before it can be considered converged
+    pub fn set_min_samples(&mut self, samples: &i32) {
+        self.min_samples = *samples;
+    }
+
+    pub fn noise_threshold(&self) -> f64 {
+        self.noise_threshold
+    }
+
+    // samples per pixel the last render took on average, None before the first one
+    pub fn average_samples(&self) -> Option<f64> {
+        if self.sample_counts.is_empty() {
+            return None;
+        }
+        let total: i64 = self.sample_counts.iter().map(|&count| count as i64).sum();
+        return Some(total as f64 / self.sample_counts.len() as f64);
+    }
+
+    // samples taken by each pixel of the last render, white where it took the most. None
+    // before the first render
+    pub fn sample_heatmap(&self) -> Option<Framebuffer> {
+        if self.sample_counts.is_empty() {
+            return None;
+        }
+        let most = self.sample_counts.iter().copied().max().unwrap_or(1).max(1);
+        let pixels = self.sample_counts.iter().map(|&count| heatmap_color(count as f64 / most as f64)).collect();
+        let width = self.sample_counts_width;
+        return Some(Framebuffer::from_pixels(width, self.sample_counts.len() / width, pixels));
+    }
+
+    // lights are sampled directly at every diffuse bounce, they have to be part of the
+    // world as well. an empty list leaves finding them to chance
+    pub fn render(&mut self, world: &dyn Hittable, lights: &HittableList) -> Framebuffer {
         self.initialize();
 
-        // file creation
-        let image_file = File::create("image.ppm")?;
-        let mut writer = BufWriter::new(image_file);
-         
-        // render
-        writer.write_all(format!("P3\n{} {}\n255\n", self.image_width, self.image_height).as_bytes())?;
-        
-        // multithreading using "rayon" crate
-        for j in 0..self.image_height {
-            println!("\rscanlines remaining: {}", (self.image_height - j));
-
-            for i in 0..self.image_width {
-                let pixel_center = self.pixel00_loc + (i as f64 * self.pixel_delta_u) + (j as f64 * self.pixel_delta_v);
-                let ray_direction = pixel_center - self.center;
-                let r = Ray::ray(&self.center, &ray_direction); 
-
-                // output to file
-                let mut pixel_color = Vec3::vec3(0.0, 0.0, 0.0);
-                for sample in 0..self.samples_per_pixel {
-                    let r = self.get_ray(i, j);
-                    pixel_color += self.ray_color(&r, self.max_depth, world);
+        // without a fixed seed every render starts from a fresh one
+        let seed = self.seed.unwrap_or_else(|| fastrand::u64(..));
+
+        // scanlines are rendered independently, across all cores using the "rayon" crate,
+        // and collected back in order so the framebuffer matches the serial path
+        let remaining = AtomicI32::new(self.image_height);
+        let render_row = |j: i32| {
+            let row = self.render_scanline(j, world, lights, seed);
+            println!("\rscanlines remaining: {}", remaining.fetch_sub(1, Ordering::Relaxed) - 1);
+            row
+        };
+
+        let rows: Vec<Vec<(Vec3, i32)>> = if self.parallel {
+            (0..self.image_height).into_par_iter().map(render_row).collect()
+        } else {
+            (0..self.image_height).map(render_row).collect()
+        };
+
+        println!("\rdone.");
+
+        let (pixels, counts): (Vec<Vec3>, Vec<i32>) = rows.into_iter().flatten().unzip();
+        self.sample_counts = counts;
+        self.sample_counts_width = self.image_width as usize;
+
+        Framebuffer::from_pixels(self.image_width as usize, self.image_height as usize, pixels)
+    }
+
+    // the color of every pixel in row j, with the number of samples it took
+    fn render_scanline(&self, j: i32, world: &dyn Hittable, lights: &HittableList, seed: u64) -> Vec<(Vec3, i32)> {
+        let mut row = Vec::with_capacity(self.image_width as usize);
+        // every pixel takes at least one sample, so none of them is divided by zero
+        let max_samples = self.samples_per_pixel.max(1);
+        for i in 0..self.image_width {
+            // every pixel draws from its own stream, so the result does not depend on
+            // which thread rendered it or in which order
+            let pixel_index = j as u64 * self.image_width as u64 + i as u64;
+            let mut rng = Rng::with_seed(mix_seed(seed, pixel_index));
+            let mut sampler = PixelSampler::pixel_sampler(self.sampler, i as u32, j as u32, max_samples as u32, seed);
+
+            let mut pixel_color = Vec3::vec3(0.0, 0.0, 0.0);
+            let mut brightness = Welford::default();
+            for s in 0..max_samples {
+                sampler.start_sample(s as u32);
+                let r = self.get_ray(i, j, &mut sampler, &mut rng);
+                let mut sample_color = self.ray_color(&r, world, lights, &mut rng);
+                if let Some(wavelength) = r.wavelength() {
+                    sample_color = wavelength_to_rgb(sample_color.x(), wavelength);
+                }
+                pixel_color += sample_color;
+
+                if self.noise_threshold > 0.0 {
+                    brightness.add(luminance(&sample_color));
+                    if brightness.count() >= self.min_samples.max(2) && brightness.relative_error() < self.noise_threshold {
+                        break;
+                    }
                 }
-                write_color(&mut writer, pixel_color, self.samples_per_pixel).expect("failed to write to file");
             }
-        } 
-        println!("\rdone.");
+            // divide the color by the number of samples
+            let samples = if self.noise_threshold > 0.0 { brightness.count() } else { max_samples };
+            row.push((pixel_color / samples as f64, samples));
+        }
 
-        Ok(())
+        return row;
     }
 
     fn initialize(&mut self) {
@@ -139,7 +272,6 @@ impl Camera {
         }
      
         // camera
-        let focal_length = (self.look_from - self.look_at).length();
         let theta = self.v_fov * (std::f64::consts::PI / 180.0);
         let h = f64::tan(theta / 2.0);
         let viewport_height = 2.0 * h * self.focus_dist;
@@ -167,61 +299,261 @@ impl Camera {
         self.defocus_disk_v = self.v * defocus_radius;
     }
 
-    fn ray_color(&self, r: &Ray, depth: i32, world: &dyn Hittable) -> Vec3 {
-        let mut rec = HitRecord::default(); 
+    // follows the path one bounce at a time, throughput is the fraction of the light found
+    // further along that makes it back to the camera
+    fn ray_color(&self, r: &Ray, world: &dyn Hittable, lights: &HittableList, rng: &mut Rng) -> Vec3 {
+        let mut color = Vec3::vec3(0.0, 0.0, 0.0);
+        let mut throughput = Vec3::vec3(1.0, 1.0, 1.0);
+        let mut ray = *r;
+        let wavelength = r.wavelength();
+        let spectrum = |color: Vec3| at_wavelength(&color, wavelength);
+        // the multiple importance sampling weight of light this ray finds by chance, the
+        // rest of it was already counted by the light sample at the previous bounce
+        let mut emission_weight = 1.0;
+        let sample_lights = !lights.is_empty();
 
-        if depth <= 0 {
-            return Vec3::vec3(0.0, 0.0, 0.0);
-        }
+        for depth in 0..self.max_depth {
+            let mut rec = HitRecord::default();
+
+            // if the ray hits nothing, add the background color
+            if !world.hit(&ray, Interval::interval(0.001, f64::INFINITY), &mut rec) {
+                color += throughput * spectrum(self.background_color(&ray));
+                break;
+            }
 
-        if world.hit(&r, Interval::interval(0.001, f64::INFINITY), &mut rec) {
-            let mut scattered = Ray::ray(&Vec3::identity(), &Vec3::identity());    
-            let mut attenuation = Vec3::identity();
-            //println("{:?}", rec.mat
-            /*match rec.mat() {
-                Materials::LAMBERTIAN(l) => {println!("{:?}", l.get_albedo());},
-                Materials::METAL(m) => {println!("{:?}", m.get_albedo());},
-            }*/
-            if match rec.mat() {
-                Materials::LAMBERTIAN(l) => {l.scatter(r, &rec, &mut attenuation, &mut scattered)},
-                Materials::METAL(m) => {m.scatter(r, &rec, &mut attenuation, &mut scattered)},
-                Materials::DIELECTRIC(d) => {d.scatter(r, &rec, &mut attenuation, &mut scattered)},
-            } {
-                //println!("{:?}", attenuation);
-                return attenuation * self.ray_color(&scattered, depth - 1, world);
+            let mut srec = ScatterRecord::default();
+            let mat = rec.mat();
+            color += throughput * emission_weight * spectrum(mat.emitted(&ray, &rec));
+
+            if !mat.scatter(&ray, &rec, &mut srec, rng) {
+                break;
+            }
+
+            if srec.skip_pdf() {
+                // specular bounces have a single direction, lights cannot be sampled for them
+                throughput = throughput * spectrum(srec.attenuation());
+                ray = *srec.skip_pdf_ray();
+                ray.set_wavelength(&wavelength);
+                ray.set_seed(&rng.u64(..));
+                emission_weight = 1.0;
             } else {
-                return Vec3::vec3(0.0, 0.0, 0.0);
+                let light_pdf = HittablePdf::hittable_pdf(lights, &rec.p());
+
+                // next event estimation, a direction towards a light weighted against the
+                // chance of the material finding it
+                if sample_lights {
+                    let direction = light_pdf.generate(rng);
+                    let light_pdf_value = light_pdf.value(&direction);
+                    let mut shadow_ray = Ray::ray_time(&rec.p(), &direction, &ray.time());
+                    shadow_ray.set_wavelength(&wavelength);
+                    shadow_ray.set_seed(&rng.u64(..));
+                    let mut light_rec = HitRecord::default();
+                    if light_pdf_value > 0.0 && world.hit(&shadow_ray, Interval::interval(0.001, f64::INFINITY), &mut light_rec) {
+                        let emitted = spectrum(light_rec.mat().emitted(&shadow_ray, &light_rec));
+                        let scattering = spectrum(mat.scattering(&ray, &rec, &srec, &shadow_ray));
+                        let weight = power_heuristic(light_pdf_value, mat.pdf_value(&ray, &rec, &srec, &direction));
+                        color += (weight / light_pdf_value) * throughput * scattering * emitted;
+                    }
+                }
+
+                let direction = mat.generate(&ray, &rec, &srec, rng);
+                let pdf_value = mat.pdf_value(&ray, &rec, &srec, &direction);
+                if pdf_value <= 0.0 {
+                    break;
+                }
+
+                let mut scattered = Ray::ray_time(&rec.p(), &direction, &ray.time());
+                scattered.set_wavelength(&wavelength);
+                scattered.set_seed(&rng.u64(..));
+                let scattering = spectrum(mat.scattering(&ray, &rec, &srec, &scattered));
+                throughput = throughput * scattering / pdf_value;
+                emission_weight = if sample_lights {
+                    power_heuristic(pdf_value, light_pdf.value(&direction))
+                } else {
+                    1.0
+                };
+                ray = scattered;
+            }
+
+            // russian roulette, dim paths are ended early and the survivors are brightened
+            // by the same odds, so on average nothing is lost
+            if depth + 1 >= self.roulette_depth {
+                let survival = f64::min(throughput.max_component(), 0.95);
+                if survival <= 0.0 || random_double(rng) >= survival {
+                    break;
+                }
+                throughput /= survival;
             }
         }
 
-        let unit_direction = unit_vector(r.direction());
-        let a = 0.5 * (unit_direction.y() + 1.0);
-        return (1.0 - a) * Vec3::vec3(1.0, 1.0, 1.0) + a * Vec3::vec3(0.5, 0.7, 1.0);
+        return color;
+    }
+
+    fn background_color(&self, r: &Ray) -> Vec3 {
+        match self.background {
+            Background::Sky => {
+                let unit_direction = unit_vector(r.direction());
+                let a = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - a) * Vec3::vec3(1.0, 1.0, 1.0) + a * Vec3::vec3(0.5, 0.7, 1.0)
+            }
+            Background::Color(color) => color,
+        }
     }
 
-    fn get_ray(&self, i: i32, j: i32) -> Ray {
+    // the sampler hands out the pixel position first, then the lens position, the time and
+    // in spectral mode the wavelength
+    fn get_ray(&self, i: i32, j: i32, sampler: &mut PixelSampler, rng: &mut Rng) -> Ray {
         let pixel_center = self.pixel00_loc + (i as f64 * self.pixel_delta_u) + (j as f64 * self.pixel_delta_v);
-        let pixel_sample = pixel_center + self.pixel_sample_square();
+        let pixel_sample = pixel_center + self.pixel_sample_square(sampler.get_2d(rng));
 
-        let ray_origin;
-        if self.defocus_angle <= 0.0 {
-            ray_origin = self.center;
+        let ray_origin = if self.defocus_angle <= 0.0 {
+            self.center
         } else {
-            ray_origin = self.defocus_disk_sample();
-        }
+            self.defocus_disk_sample(sampler.get_2d(rng))
+        };
         let ray_direction = pixel_sample - ray_origin;
-        let ray_time = random_double();
+        let ray_time = sampler.get_1d(rng);
 
-        return Ray::ray_time(&ray_origin, &ray_direction, &ray_time);
+        let mut ray = Ray::ray_time(&ray_origin, &ray_direction, &ray_time);
+        ray.set_seed(&rng.u64(..));
+        if self.spectral {
+            ray.set_wavelength(&Some(sample_wavelength(sampler.get_1d(rng))));
+        }
+        return ray;
     }
-    fn defocus_disk_sample(&self) -> Vec3 {
-        let p = random_in_unit_disk();
+    fn defocus_disk_sample(&self, (u, v): (f64, f64)) -> Vec3 {
+        let p = sample_unit_disk(u, v);
         return self.center + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v); 
     }
 
-    fn pixel_sample_square(&self) -> Vec3 {
-        let px = -0.5 * random_double();
-        let py = -0.5 * random_double();
+    // a point in the one pixel square centered on the pixel center, half a pixel to either side
+    fn pixel_sample_square(&self, (u, v): (f64, f64)) -> Vec3 {
+        let px = u - 0.5;
+        let py = v - 0.5;
         return (px * self.pixel_delta_u) + (py * self.pixel_delta_v);
     }
 }
+
+// running mean and variance of the samples of a pixel
+#[derive(Default)]
+struct Welford {
+    count: i32,
+    mean: f64,
+    m2: f64,
+}
+
+impl Welford {
+    fn add(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    fn count(&self) -> i32 {
+        self.count
+    }
+
+    // standard error of the mean relative to the mean, which is kept away from zero so
+    // black pixels can converge
+    fn relative_error(&self) -> f64 {
+        let variance = self.m2 / (self.count - 1) as f64;
+        return f64::sqrt(variance / self.count as f64) / f64::max(self.mean, 0.01);
+    }
+}
+
+// black through blue, red and yellow to white as t goes from 0 to 1
+fn heatmap_color(t: f64) -> Vec3 {
+    let stops = [
+        Vec3::vec3(0.0, 0.0, 0.0),
+        Vec3::vec3(0.0, 0.0, 1.0),
+        Vec3::vec3(1.0, 0.0, 0.0),
+        Vec3::vec3(1.0, 1.0, 0.0),
+        Vec3::vec3(1.0, 1.0, 1.0),
+    ];
+    let x = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+    let i = usize::min(x as usize, stops.len() - 2);
+    let c = stops[i] + (x - i as f64) * (stops[i + 1] - stops[i]);
+    // the stops are display colors, the framebuffer is linear
+    return Vec3::vec3(gamma_to_linear(c.x()), gamma_to_linear(c.y()), gamma_to_linear(c.z()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::Sphere;
+    use std::sync::Arc;
+
+    // a diffuse sphere under a spherical light, the light is sampled directly as well
+    fn lit_sphere() -> (HittableList, HittableList) {
+        let white: Arc<dyn Material> = Arc::new(Lambertian::new(Vec3::vec3(0.8, 0.8, 0.8)));
+        let light: Arc<dyn Material> = Arc::new(DiffuseLight::new(Vec3::vec3(4.0, 4.0, 4.0)));
+        let light_center = Vec3::vec3(0.0, 1.5, 1.0);
+
+        let mut world = HittableList::hittable_list();
+        world.add(Box::new(Sphere::sphere(Vec3::vec3(0.0, 0.0, 0.0), 0.5, white)));
+        world.add(Box::new(Sphere::sphere(light_center, 0.5, light.clone())));
+        let mut lights = HittableList::hittable_list();
+        lights.add(Box::new(Sphere::sphere(light_center, 0.5, light)));
+        return (world, lights);
+    }
+
+    fn tiny_camera() -> Camera {
+        let mut cam = Camera::camera();
+        cam.set_image_width(&8);
+        cam.set_samples_per_pixel(&4);
+        cam.set_max_depth(&8);
+        cam.set_fov(&40.0);
+        cam.set_look_from(&Vec3::vec3(0.0, 0.0, 3.0));
+        cam.set_focus_dist(&3.0);
+        cam.set_background(&Background::Color(Vec3::vec3(0.0, 0.0, 0.0)));
+        cam.set_seed(&7);
+        return cam;
+    }
+
+    fn same_pixels(a: &Framebuffer, b: &Framebuffer) -> bool {
+        a.width() == b.width()
+            && a.height() == b.height()
+            && a.pixels().iter().zip(b.pixels()).all(|(p, q)| p.x() == q.x() && p.y() == q.y() && p.z() == q.z())
+    }
+
+    #[test]
+    fn parallel_render_matches_serial() {
+        let (world, lights) = lit_sphere();
+
+        let mut cam = tiny_camera();
+        cam.set_parallel(&false);
+        let serial = cam.render(&world, &lights);
+        cam.set_parallel(&true);
+        let parallel = cam.render(&world, &lights);
+
+        assert!(serial.pixels().iter().any(|p| p.x() > 0.0), "the scene rendered black");
+        assert!(same_pixels(&serial, &parallel));
+    }
+
+    #[test]
+    fn adaptive_sampling_stops_early_only_where_converged() {
+        // a large light beside the sphere that it only finds by chance, the side of it
+        // facing the light stays noisy. the flat background on the left converges as
+        // soon as it is allowed to
+        let white: Arc<dyn Material> = Arc::new(Lambertian::new(Vec3::vec3(0.8, 0.8, 0.8)));
+        let light: Arc<dyn Material> = Arc::new(DiffuseLight::new(Vec3::vec3(4.0, 4.0, 4.0)));
+        let mut world = HittableList::hittable_list();
+        world.add(Box::new(Sphere::sphere(Vec3::vec3(0.0, 0.0, 0.0), 0.5, white)));
+        world.add(Box::new(Sphere::sphere(Vec3::vec3(2.0, 0.0, 0.5), 1.4, light)));
+
+        let mut cam = tiny_camera();
+        cam.set_samples_per_pixel(&64);
+        cam.set_min_samples(&16);
+        cam.set_noise_threshold(&0.05);
+        cam.render(&world, &HittableList::hittable_list());
+
+        let width = cam.sample_counts_width;
+        let count = |x: usize, y: usize| cam.sample_counts[y * width + x];
+        assert_eq!(count(0, 0), 16);
+        assert_eq!(count(0, 7), 16);
+        for (x, y) in [(4, 3), (4, 4), (5, 3), (5, 4)] {
+            assert_eq!(count(x, y), 64);
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::path::PathBuf;
+use raytracing::camera::Camera;
+use raytracing::output::ImageFormat;
+use raytracing::sampler::SamplerKind;
+use raytracing::scene::parse_number;
+
+pub const USAGE: &str = "\
+usage: raytracing [options] [scene file]
+
+renders the scene file, or the built in random spheres scene when none is given
+
+options:
+  -o, --output <path>     output image path (default: image.ppm, which is only
+                          replaced with --force)
+      --force             overwrite image.ppm when no --output is given
+  -f, --format <format>   ppm, p6, png, png16, pfm or hdr (default: from the output extension)
+  -w, --width <pixels>    image width
+  -a, --aspect <ratio>    aspect ratio, e.g. 1.5 or 16/9
+  -s, --spp <samples>     samples per pixel
+  -d, --max-depth <n>     maximum number of ray bounces
+  -r, --roulette-depth <n>
+                          bounces before russian roulette can end a path (default: 3)
+  -t, --threads <n>       number of render threads (default: all cores)
+      --seed <n>          seed for reproducible renders
+      --sampler <name>    random, stratified, halton, sobol or blue_noise (default: sobol)
+      --noise-threshold <t>
+                          adaptive sampling, pixels stop once their relative noise is
+                          below t, e.g. 0.01, --spp is then the most a pixel takes
+      --min-spp <n>       samples every pixel takes before it can stop (default: 16)
+      --heatmap <path>    also save an image of the samples taken by each pixel
+      --spectral          trace a wavelength per path, glass disperses light
+  -h, --help              print this summary
+
+command line values override the ones in the scene file";
+
+pub enum Command {
+    Render(Options),
+    Help,
+}
+
+pub struct Options {
+    scene: Option<PathBuf>,
+    output: PathBuf,
+    // whether an existing file at output may be replaced
+    overwrite: bool,
+    format: ImageFormat,
+    image_width: Option<i32>,
+    aspect_ratio: Option<f64>,
+    samples_per_pixel: Option<i32>,
+    max_depth: Option<i32>,
+    roulette_depth: Option<i32>,
+    threads: Option<usize>,
+    seed: Option<u64>,
+    sampler: Option<SamplerKind>,
+    noise_threshold: Option<f64>,
+    min_samples: Option<i32>,
+    heatmap: Option<(PathBuf, ImageFormat)>,
+    spectral: bool,
+}
+
+impl Options {
+    pub fn scene(&self) -> Option<&PathBuf> {
+        self.scene.as_ref()
+    }
+
+    pub fn output(&self) -> &PathBuf {
+        &self.output
+    }
+
+    pub fn overwrite(&self) -> bool {
+        self.overwrite
+    }
+
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    pub fn threads(&self) -> Option<usize> {
+        self.threads
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn heatmap(&self) -> Option<&(PathBuf, ImageFormat)> {
+        self.heatmap.as_ref()
+    }
+
+    pub fn apply_overrides(&self, cam: &mut Camera) {
+        if let Some(width) = self.image_width {
+            cam.set_image_width(&width);
+        }
+        if let Some(ratio) = self.aspect_ratio {
+            cam.set_aspect_ratio(&ratio);
+        }
+        if let Some(samples) = self.samples_per_pixel {
+            cam.set_samples_per_pixel(&samples);
+        }
+        if let Some(depth) = self.max_depth {
+            cam.set_max_depth(&depth);
+        }
+        if let Some(depth) = self.roulette_depth {
+            cam.set_roulette_depth(&depth);
+        }
+        if let Some(seed) = self.seed {
+            cam.set_seed(&seed);
+        }
+        if let Some(sampler) = self.sampler {
+            cam.set_sampler(&sampler);
+        }
+        if let Some(threshold) = self.noise_threshold {
+            cam.set_noise_threshold(&threshold);
+        }
+        if let Some(samples) = self.min_samples {
+            cam.set_min_samples(&samples);
+        }
+        if self.spectral {
+            cam.set_spectral(&true);
+        }
+    }
+}
+
+// args excludes the program name
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let mut scene = None;
+    let mut output = None;
+    let mut format = None;
+    let mut image_width = None;
+    let mut aspect_ratio = None;
+    let mut samples_per_pixel = None;
+    let mut max_depth = None;
+    let mut roulette_depth = None;
+    let mut threads = None;
+    let mut seed = None;
+    let mut sampler = None;
+    let mut noise_threshold = None;
+    let mut min_samples = None;
+    let mut heatmap = None;
+    let mut spectral = false;
+    let mut force = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or(format!("missing value for '{}'", arg));
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "-f" | "--format" => {
+                let name = value()?;
+                format = Some(ImageFormat::from_name(name).ok_or(format!("unknown image format '{}'", name))?);
+            }
+            "-w" | "--width" => image_width = Some(positive_integer(arg, value()?)?),
+            "-a" | "--aspect" => {
+                let ratio = parse_number(value()?)?;
+                if ratio <= 0.0 {
+                    return Err(format!("{} must be positive", arg));
+                }
+                aspect_ratio = Some(ratio);
+            }
+            "-s" | "--spp" => samples_per_pixel = Some(positive_integer(arg, value()?)?),
+            "-d" | "--max-depth" => max_depth = Some(positive_integer(arg, value()?)?),
+            "-r" | "--roulette-depth" => roulette_depth = Some(positive_integer(arg, value()?)?),
+            "-t" | "--threads" => threads = Some(positive_integer(arg, value()?)? as usize),
+            "--seed" => {
+                let text = value()?;
+                seed = Some(text.parse::<u64>().map_err(|_| format!("--seed expects a whole number, found '{}'", text))?);
+            }
+            "--sampler" => {
+                let name = value()?;
+                sampler = Some(SamplerKind::from_name(name).ok_or(format!("unknown sampler '{}'", name))?);
+            }
+            "--noise-threshold" => {
+                let threshold = parse_number(value()?)?;
+                if threshold <= 0.0 {
+                    return Err(format!("{} must be positive", arg));
+                }
+                noise_threshold = Some(threshold);
+            }
+            "--min-spp" => min_samples = Some(positive_integer(arg, value()?)?),
+            "--heatmap" => {
+                let path = PathBuf::from(value()?);
+                let format = ImageFormat::from_path(&path)
+                    .ok_or(format!("cannot tell the image format of '{}'", path.display()))?;
+                heatmap = Some((path, format));
+            }
+            "--spectral" => spectral = true,
+            "--force" => force = true,
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ => {
+                if scene.is_some() {
+                    return Err(format!("unexpected argument '{}', only one scene file can be rendered", arg));
+                }
+                scene = Some(PathBuf::from(arg));
+            }
+        }
+    }
+
+    // a path given on the command line is meant to be written, the default one is not
+    // replaced by accident
+    let overwrite = force || output.is_some();
+    let output = output.unwrap_or(PathBuf::from("image.ppm"));
+    let format = match format {
+        Some(format) => format,
+        None => ImageFormat::from_path(&output)
+            .ok_or(format!("cannot tell the image format of '{}', pass --format", output.display()))?,
+    };
+
+    Ok(Command::Render(Options {
+        scene,
+        output,
+        overwrite,
+        format,
+        image_width,
+        aspect_ratio,
+        samples_per_pixel,
+        max_depth,
+        roulette_depth,
+        threads,
+        seed,
+        sampler,
+        noise_threshold,
+        min_samples,
+        heatmap,
+        spectral,
+    }))
+}
+
+fn positive_integer(option: &str, text: &str) -> Result<i32, String> {
+    match text.parse::<i32>() {
+        Ok(value) if value > 0 => Ok(value),
+        _ => Err(format!("{} expects a positive whole number, found '{}'", option, text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raytracing::scene::parse_scene;
+
+    fn parse(line: &str) -> Result<Options, String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        match parse_args(&args)? {
+            Command::Render(options) => Ok(options),
+            Command::Help => Err("help".to_string()),
+        }
+    }
+
+    fn parse_error(line: &str) -> String {
+        match parse(line) {
+            Ok(_) => panic!("'{}' parsed", line),
+            Err(message) => message,
+        }
+    }
+
+    #[test]
+    fn command_line_overrides_the_scene() {
+        let scene = parse_scene("image_width 50\naspect_ratio 2\nsamples_per_pixel 7\nnoise_threshold 0.5\n").unwrap_or_else(|e| panic!("{}", e));
+        let (mut cam, world, lights) = scene.into_parts();
+        parse("scene.txt -w 4 --spp 3 --seed 1").unwrap().apply_overrides(&mut cam);
+
+        // the width and samples come from the command line, the aspect ratio and noise
+        // threshold it leaves alone from the scene
+        assert_eq!(cam.noise_threshold(), 0.5);
+        cam.set_noise_threshold(&0.0);
+        let image = cam.render(&world, &lights);
+        assert_eq!((image.width(), image.height()), (4, 2));
+        assert_eq!(cam.average_samples(), Some(3.0));
+    }
+
+    #[test]
+    fn zero_samples_are_rejected() {
+        assert_eq!(parse_error("--spp 0"), "--spp expects a positive whole number, found '0'");
+        assert_eq!(parse_error("-s -4"), "-s expects a positive whole number, found '-4'");
+    }
+
+    #[test]
+    fn output_format_comes_from_the_extension() {
+        assert_eq!(parse("-o out.png").unwrap().format(), ImageFormat::Png);
+        assert_eq!(parse("-o out.jpg -f p6").unwrap().format(), ImageFormat::PpmBinary);
+        assert_eq!(parse_error("-o out.jpg"), "cannot tell the image format of 'out.jpg', pass --format");
+        assert_eq!(parse_error("--heatmap samples.tiff"), "cannot tell the image format of 'samples.tiff'");
+        assert_eq!(parse_error("-f jpg"), "unknown image format 'jpg'");
+    }
+
+    #[test]
+    fn default_output_is_not_overwritten() {
+        assert!(!parse("").unwrap().overwrite());
+        assert!(parse("--force").unwrap().overwrite());
+        assert!(parse("-o image.ppm").unwrap().overwrite());
+    }
+}
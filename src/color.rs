@@ -1,34 +1,39 @@
 use crate::vec3::Vec3;
-use std::fs::File;
-use std::io::{BufWriter, Write}; 
+use std::io::Write; 
 use crate::interval::Interval;
 
-fn linear_to_gamma(linear_component: f64) -> f64 {
+pub fn linear_to_gamma(linear_component: f64) -> f64 {
     return linear_component.sqrt();
 }
 
-pub fn write_color(file: &mut BufWriter<File>, pixel_color: Vec3, samples_per_pixel: i32) -> std::io::Result<()> {
-    let mut r = pixel_color.x();
-    let mut g = pixel_color.y();
-    let mut b = pixel_color.z();
+// inverse of linear_to_gamma, for reading 8-bit images back into linear space
+pub fn gamma_to_linear(gamma_component: f64) -> f64 {
+    return gamma_component * gamma_component;
+}
 
-    // divide the color by the number of samples
-    let scale = 1.0 / samples_per_pixel as f64;
-    r *= scale; 
-    g *= scale;
-    b *= scale;
+// rec. 709 weights, how bright a linear color looks
+pub fn luminance(color: &Vec3) -> f64 {
+    return 0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z();
+}
 
-    // apply gamma correction
-    r = linear_to_gamma(r);
-    g = linear_to_gamma(g);
-    b = linear_to_gamma(b);
+// gamma corrected, translated [0, 255] value of a linear color component
+pub fn to_u8(linear_component: f64) -> u8 {
+    let intensity = Interval::interval(0.000, 0.999);
+    (256.0 * intensity.clamp(linear_to_gamma(linear_component))) as u8
+}
 
+// gamma corrected, translated [0, 65535] value of a linear color component
+pub fn to_u16(linear_component: f64) -> u16 {
+    let intensity = Interval::interval(0.0, 1.0);
+    (65535.0 * intensity.clamp(linear_to_gamma(linear_component))).round() as u16
+}
+
+pub fn write_color<W: Write>(out: &mut W, pixel_color: Vec3) -> std::io::Result<()> {
     // write the translated [0, 255] value of each color component
-    let intensity = Interval::interval(0.000, 0.999);
-    file.write_all(format!("{} {} {}\n",
-                           (256.0 * intensity.clamp(r)) as i32,
-                           (256.0 * intensity.clamp(g)) as i32,
-                           (256.0 * intensity.clamp(b)) as i32).as_bytes())?;
+    out.write_all(format!("{} {} {}\n",
+                          to_u8(pixel_color.x()),
+                          to_u8(pixel_color.y()),
+                          to_u8(pixel_color.z())).as_bytes())?;
 
     Ok(())
 }
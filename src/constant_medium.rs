@@ -0,0 +1,92 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::ray::Ray;
+use crate::vec3::*;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::*;
+use crate::texture::*;
+use crate::utility::mix_seed;
+use crate::aabb::AABB;
+
+// volume of constant density inside a closed boundary shape, e.g. smoke or fog. rays are
+// scattered at an exponentially distributed distance, by the phase function material
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+    // sets the random distances apart from those of other media the same ray goes through
+    id: u64,
+}
+
+static NEXT_MEDIUM_ID: AtomicU64 = AtomicU64::new(0);
+
+impl ConstantMedium {
+    pub fn constant_medium(boundary: Arc<dyn Hittable>, density: f64, phase_function: Arc<dyn Material>) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+            id: NEXT_MEDIUM_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    pub fn from_texture(boundary: Arc<dyn Hittable>, density: f64, tex: Textures) -> ConstantMedium {
+        ConstantMedium::constant_medium(boundary, density, Arc::new(Isotropic::from_texture(tex)))
+    }
+
+    pub fn from_color(boundary: Arc<dyn Hittable>, density: f64, albedo: Vec3) -> ConstantMedium {
+        ConstantMedium::constant_medium(boundary, density, Arc::new(Isotropic::new(albedo)))
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let mut rec1 = HitRecord::default();
+        let mut rec2 = HitRecord::default();
+
+        // find where the ray enters and leaves the boundary, which also works from the inside
+        if !self.boundary.hit(r, Interval::universe(), &mut rec1) {
+            return false;
+        }
+        if !self.boundary.hit(r, Interval::interval(rec1.t() + 0.0001, f64::INFINITY), &mut rec2) {
+            return false;
+        }
+
+        let t_enter = f64::max(f64::max(rec1.t(), ray_t.min()), 0.0);
+        let t_exit = f64::min(rec2.t(), ray_t.max());
+        if t_enter >= t_exit {
+            return false;
+        }
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        // hit has no random generator, the distance is drawn from the seed the camera gives
+        // every ray from the random stream of its path. it is mixed with the medium and
+        // where the ray enters it, so volumes along the same ray scatter independently
+        let hash = mix_seed(mix_seed(r.seed(), self.id), t_enter.to_bits());
+        // in (0, 1] so the logarithm is finite
+        let u = 1.0 - (hash >> 11) as f64 / (1u64 << 53) as f64;
+        let hit_distance = self.neg_inv_density * f64::ln(u);
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        rec.set_t(&t);
+        rec.set_p(&r.at(&t));
+        rec.set_mat(&self.phase_function);
+
+        // there is no surface, the normal and side are arbitrary
+        rec.set_normal(&Vec3::vec3(1.0, 0.0, 0.0));
+        rec.set_tangents(&Vec3::vec3(0.0, 1.0, 0.0), &Vec3::vec3(0.0, 0.0, 1.0));
+        rec.set_front_face(&true);
+        rec.set_uv(&0.0, &0.0);
+
+        return true;
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.boundary.bounding_box()
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::Arc;
+use crate::ray::Ray;
+use crate::vec3::*;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::*;
+use crate::aabb::AABB;
+use crate::onb::Onb;
+use crate::utility::*;
+
+pub struct Disk {
+    center: Vec3,
+    radius: f64,
+    mat: Arc<dyn Material>,
+    frame: Onb,
+    bbox: AABB,
+    area: f64,
+}
+
+impl Disk {
+    pub fn disk(_center: Vec3, _normal: Vec3, _radius: f64, _material: Arc<dyn Material>) -> Disk {
+        let frame = Onb::onb(&_normal);
+
+        // the disk extends radius * sqrt(1 - n_i^2) along each world axis
+        let n = frame.w();
+        let extent = Vec3::vec3(
+            _radius * f64::sqrt(f64::max(0.0, 1.0 - n.x() * n.x())),
+            _radius * f64::sqrt(f64::max(0.0, 1.0 - n.y() * n.y())),
+            _radius * f64::sqrt(f64::max(0.0, 1.0 - n.z() * n.z())),
+        );
+
+        Disk {
+            center: _center,
+            radius: _radius,
+            mat: _material,
+            frame,
+            bbox: AABB::aabb(&(_center - extent), &(_center + extent)).pad_to_minimums(),
+            area: std::f64::consts::PI * _radius * _radius,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let normal = self.frame.w();
+        let denom = dot(&normal, &r.direction());
+
+        // no hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = dot(&normal, &(self.center - r.origin())) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        let intersection = r.at(&t);
+        let local = self.frame.to_local(&(intersection - self.center));
+        let dist_squared = local.x() * local.x() + local.y() * local.y();
+        if dist_squared > self.radius * self.radius {
+            return false;
+        }
+
+        // u is the angle around the normal and v the distance from the center, both in [0,1]
+        let pi = std::f64::consts::PI;
+        let rho = dist_squared.sqrt();
+        let phi = f64::atan2(local.y(), local.x()) + pi;
+        let u = phi / (2.0 * pi);
+        let v = rho / self.radius;
+
+        // p = center + rho (cos(phi) frame.u + sin(phi) frame.v), differentiated directly so
+        // the result does not depend on the handedness of the frame. the angular tangent
+        // vanishes at the center, any direction in the plane will do there
+        let (radial, angular) = if rho > 1e-8 {
+            let (cos_phi, sin_phi) = (local.x() / rho, local.y() / rho);
+            (cos_phi * self.frame.u() + sin_phi * self.frame.v(), -sin_phi * self.frame.u() + cos_phi * self.frame.v())
+        } else {
+            (self.frame.u(), self.frame.v())
+        };
+        let dpdu = 2.0 * pi * f64::max(rho, 1e-8) * angular;
+        let dpdv = self.radius * radial;
+
+        rec.set_t(&t);
+        rec.set_p(&intersection);
+        rec.set_mat(&self.mat);
+        rec.set_face_normal(r, &normal);
+        rec.set_uv(&u, &v);
+        rec.set_tangents(&dpdu, &dpdv);
+
+        return true;
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        if !self.hit(&Ray::ray(origin, direction), Interval::interval(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t() * rec.t() * direction.length_squared();
+        let cosine = f64::abs(dot(direction, &rec.normal()) / direction.length());
+
+        return distance_squared / (cosine * self.area);
+    }
+
+    fn random(&self, origin: &Vec3, rng: &mut Rng) -> Vec3 {
+        // the square root keeps the points uniform over the area
+        let rho = self.radius * f64::sqrt(random_double(rng));
+        let phi = 2.0 * std::f64::consts::PI * random_double(rng);
+        let p = self.center + self.frame.transform(&Vec3::vec3(rho * phi.cos(), rho * phi.sin(), 0.0));
+        return p - *origin;
+    }
+}
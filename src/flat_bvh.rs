@@ -0,0 +1,428 @@
+use std::fmt;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use crate::aabb::AABB;
+
+// relative costs used by the surface area heuristic
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECT_COST: f64 = 1.0;
+
+const BUCKET_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+
+// traversal uses a fixed size stack, so the build never goes deeper than this
+const MAX_DEPTH: usize = 64;
+
+#[derive(Copy, Clone)]
+struct FlatNode {
+    bbox: AABB,
+    // leaves: index of the first object, interior nodes: index of the second child
+    // (the first child always directly follows its parent)
+    offset: usize,
+    // number of objects in a leaf, zero for interior nodes
+    count: usize,
+    axis: usize,
+}
+
+#[derive(Copy, Clone)]
+struct PrimitiveInfo {
+    index: usize,
+    bbox: AABB,
+    centroid: Vec3,
+}
+
+#[derive(Copy, Clone)]
+struct Bucket {
+    count: usize,
+    bbox: AABB,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BvhStats {
+    primitive_count: usize,
+    node_count: usize,
+    leaf_count: usize,
+    max_depth: usize,
+    min_leaf_size: usize,
+    max_leaf_size: usize,
+    sah_cost: f64,
+}
+
+impl BvhStats {
+    pub fn primitive_count(&self) -> usize {
+        self.primitive_count
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn min_leaf_size(&self) -> usize {
+        self.min_leaf_size
+    }
+
+    pub fn max_leaf_size(&self) -> usize {
+        self.max_leaf_size
+    }
+
+    pub fn average_leaf_size(&self) -> f64 {
+        if self.leaf_count == 0 {
+            return 0.0;
+        }
+        self.primitive_count as f64 / self.leaf_count as f64
+    }
+
+    // expected cost of tracing a ray through the tree, in the same units as linear_cost
+    pub fn sah_cost(&self) -> f64 {
+        self.sah_cost
+    }
+
+    // expected cost of the linear scan done by HittableList::hit
+    pub fn linear_cost(&self) -> f64 {
+        self.primitive_count as f64 * INTERSECT_COST
+    }
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bvh: {} primitives, {} nodes, {} leaves, depth {}, leaf size {}..{} (avg {:.2}), sah cost {:.2} (linear {:.2})",
+               self.primitive_count,
+               self.node_count,
+               self.leaf_count,
+               self.max_depth,
+               self.min_leaf_size,
+               self.max_leaf_size,
+               self.average_leaf_size(),
+               self.sah_cost,
+               self.linear_cost())
+    }
+}
+
+// the primitives can be any hittable type, meshes use this to avoid boxing every triangle
+pub struct FlatBvh<T: Hittable = Box<dyn Hittable>> {
+    objects: Vec<T>,
+    nodes: Vec<FlatNode>,
+    stats: BvhStats,
+}
+
+impl FlatBvh {
+    pub fn flat_bvh(list: HittableList) -> FlatBvh {
+        FlatBvh::from_objects(list.into_objects())
+    }
+}
+
+impl<T: Hittable> FlatBvh<T> {
+    pub fn from_objects(objects: Vec<T>) -> FlatBvh<T> {
+        let mut prims: Vec<PrimitiveInfo> = objects.iter().enumerate().map(|(index, object)| {
+            let bbox = object.bounding_box();
+            PrimitiveInfo {
+                index,
+                bbox,
+                centroid: bbox.centroid(),
+            }
+        }).collect();
+
+        let mut stats = BvhStats {
+            primitive_count: objects.len(),
+            min_leaf_size: usize::MAX,
+            ..BvhStats::default()
+        };
+        let mut nodes = Vec::with_capacity(2 * objects.len());
+        if !prims.is_empty() {
+            build(&mut nodes, &mut prims, 0, 0, &mut stats);
+        }
+        if stats.leaf_count == 0 {
+            stats.min_leaf_size = 0;
+        }
+
+        // reorder the objects so every leaf references a contiguous range
+        let mut slots: Vec<Option<T>> = objects.into_iter().map(Some).collect();
+        let objects = prims.iter().map(|p| slots[p.index].take().expect("object referenced twice")).collect();
+
+        // normalise the summed cost by the surface area of the root
+        if let Some(root) = nodes.first() {
+            let root_area = root.bbox.surface_area();
+            if root_area > 0.0 && root_area.is_finite() {
+                stats.sah_cost /= root_area;
+            }
+        }
+
+        FlatBvh {
+            objects,
+            nodes,
+            stats,
+        }
+    }
+
+    pub fn stats(&self) -> &BvhStats {
+        &self.stats
+    }
+}
+
+// builds the subtree over prims, which starts at index first of the final object order
+fn build(nodes: &mut Vec<FlatNode>, prims: &mut [PrimitiveInfo], first: usize, depth: usize, stats: &mut BvhStats) -> usize {
+    let node_index = nodes.len();
+
+    let mut bbox = AABB::identity();
+    let mut centroid_bounds = AABB::identity();
+    for p in prims.iter() {
+        bbox = AABB::aabb_boxes(&bbox, &p.bbox);
+        centroid_bounds = AABB::aabb_boxes(&centroid_bounds, &AABB::aabb(&p.centroid, &p.centroid));
+    }
+
+    nodes.push(FlatNode {
+        bbox,
+        offset: 0,
+        count: 0,
+        axis: 0,
+    });
+    stats.node_count += 1;
+    stats.max_depth = usize::max(stats.max_depth, depth);
+
+    let axis = centroid_bounds.longest_axis();
+    let extent = centroid_bounds.axis(axis);
+    let split = if prims.len() <= 1 || extent.size() <= 0.0 || depth + 1 >= MAX_DEPTH {
+        None
+    } else {
+        find_split(prims, &bbox, axis, extent)
+    };
+
+    let mid = match split {
+        Some(mid) => mid,
+        None => {
+            nodes[node_index].count = prims.len();
+            nodes[node_index].offset = first;
+            stats.leaf_count += 1;
+            stats.min_leaf_size = usize::min(stats.min_leaf_size, prims.len());
+            stats.max_leaf_size = usize::max(stats.max_leaf_size, prims.len());
+            stats.sah_cost += finite_area(&bbox) * prims.len() as f64 * INTERSECT_COST;
+            return node_index;
+        }
+    };
+
+    stats.sah_cost += finite_area(&bbox) * TRAVERSAL_COST;
+    let (lower, upper) = prims.split_at_mut(mid);
+    build(nodes, lower, first, depth + 1, stats);
+    let second = build(nodes, upper, first + mid, depth + 1, stats);
+    nodes[node_index].offset = second;
+    nodes[node_index].axis = axis;
+
+    return node_index;
+}
+
+// bins the centroids along the axis and returns the partition point of the cheapest split,
+// or None when keeping the objects in a single leaf is cheaper
+fn find_split(prims: &mut [PrimitiveInfo], bbox: &AABB, axis: usize, extent: Interval) -> Option<usize> {
+    let bucket_of = |p: &PrimitiveInfo| {
+        let b = ((p.centroid[axis] - extent.min()) / extent.size() * BUCKET_COUNT as f64) as usize;
+        usize::min(b, BUCKET_COUNT - 1)
+    };
+
+    let mut buckets = [Bucket { count: 0, bbox: AABB::identity() }; BUCKET_COUNT];
+    for p in prims.iter() {
+        let b = bucket_of(p);
+        buckets[b].count += 1;
+        buckets[b].bbox = AABB::aabb_boxes(&buckets[b].bbox, &p.bbox);
+    }
+
+    // sweep from both sides to get the area and count below/above every split plane
+    let mut below = [(0usize, 0.0f64); BUCKET_COUNT - 1];
+    let mut acc = Bucket { count: 0, bbox: AABB::identity() };
+    for i in 0..BUCKET_COUNT - 1 {
+        acc.count += buckets[i].count;
+        acc.bbox = AABB::aabb_boxes(&acc.bbox, &buckets[i].bbox);
+        below[i] = (acc.count, if acc.count > 0 { finite_area(&acc.bbox) } else { 0.0 });
+    }
+
+    let mut best_cost = f64::INFINITY;
+    let mut best_bucket = 0;
+    let mut acc = Bucket { count: 0, bbox: AABB::identity() };
+    for i in (1..BUCKET_COUNT).rev() {
+        acc.count += buckets[i].count;
+        acc.bbox = AABB::aabb_boxes(&acc.bbox, &buckets[i].bbox);
+        let (count_below, area_below) = below[i - 1];
+        if count_below == 0 || acc.count == 0 {
+            continue;
+        }
+        let cost = count_below as f64 * area_below + acc.count as f64 * finite_area(&acc.bbox);
+        if cost < best_cost {
+            best_cost = cost;
+            best_bucket = i - 1;
+        }
+    }
+
+    let area = finite_area(bbox);
+    let split_cost = if area > 0.0 {
+        TRAVERSAL_COST + best_cost / area * INTERSECT_COST
+    } else {
+        TRAVERSAL_COST + best_cost
+    };
+    let leaf_cost = prims.len() as f64 * INTERSECT_COST;
+
+    if best_cost.is_finite() && (prims.len() > MAX_LEAF_SIZE || split_cost < leaf_cost) {
+        let mid = partition(prims, |p| bucket_of(p) <= best_bucket);
+        if mid > 0 && mid < prims.len() {
+            return Some(mid);
+        }
+    }
+
+    if prims.len() > MAX_LEAF_SIZE {
+        // binning could not separate the objects, fall back to a median split
+        let mid = prims.len() / 2;
+        prims.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+        return Some(mid);
+    }
+
+    return None;
+}
+
+fn partition(prims: &mut [PrimitiveInfo], pred: impl Fn(&PrimitiveInfo) -> bool) -> usize {
+    let mut first = 0;
+    for i in 0..prims.len() {
+        if pred(&prims[i]) {
+            prims.swap(first, i);
+            first += 1;
+        }
+    }
+    return first;
+}
+
+// unbounded objects would turn every cost into infinity, so they only count as their finite part
+fn finite_area(bbox: &AABB) -> f64 {
+    let area = bbox.surface_area();
+    if area.is_finite() { area } else { 0.0 }
+}
+
+impl<T: Hittable> Hittable for FlatBvh<T> {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let direction = r.direction();
+        let dir_is_neg = [direction.x() < 0.0, direction.y() < 0.0, direction.z() < 0.0];
+
+        let mut hit_anything = false;
+        let mut closest_so_far = ray_t.max();
+        let mut stack = [0usize; MAX_DEPTH];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+            if node.bbox.hit(r, Interval::interval(ray_t.min(), closest_so_far)) {
+                if node.count > 0 {
+                    for object in self.objects[node.offset..node.offset + node.count].iter() {
+                        if object.hit(r, Interval::interval(ray_t.min(), closest_so_far), rec) {
+                            hit_anything = true;
+                            closest_so_far = rec.t();
+                        }
+                    }
+                } else {
+                    // visit the child closest to the ray origin first
+                    if dir_is_neg[node.axis] {
+                        stack[stack_len] = current + 1;
+                        current = node.offset;
+                    } else {
+                        stack[stack_len] = node.offset;
+                        current += 1;
+                    }
+                    stack_len += 1;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+
+        return hit_anything;
+    }
+
+    fn bounding_box(&self) -> AABB {
+        match self.nodes.first() {
+            Some(root) => root.bbox,
+            None => AABB::identity(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::material::{Lambertian, Material};
+    use crate::plane::Plane;
+    use crate::sphere::Sphere;
+    use crate::triangle::Triangle;
+    use crate::utility::{random_double_range, Rng};
+    use crate::vec3::random_unit_vector;
+
+    // random spheres and triangles, each with its own material so a hit can be traced back
+    // to the object. the same seed and materials give the same objects
+    fn random_objects(seed: u64, materials: &[Arc<dyn Material>], with_plane: bool) -> HittableList {
+        let mut rng = Rng::with_seed(seed);
+        let mut list = HittableList::hittable_list();
+        let mut materials = materials.iter().cloned();
+        for _ in 0..100 {
+            let center = Vec3::random_range(-10.0, 10.0, &mut rng);
+            let radius = random_double_range(0.1, 1.0, &mut rng);
+            list.add(Box::new(Sphere::sphere(center, radius, materials.next().unwrap())));
+
+            let a = Vec3::random_range(-10.0, 10.0, &mut rng);
+            let b = a + Vec3::random_range(-2.0, 2.0, &mut rng);
+            let c = a + Vec3::random_range(-2.0, 2.0, &mut rng);
+            list.add(Box::new(Triangle::triangle(a, b, c, materials.next().unwrap())));
+        }
+        if with_plane {
+            list.add(Box::new(Plane::plane(Vec3::vec3(0.0, -5.0, 0.0), Vec3::vec3(0.1, 1.0, 0.2), materials.next().unwrap())));
+        }
+        return list;
+    }
+
+    fn matches_linear_scan(with_plane: bool) {
+        let materials: Vec<Arc<dyn Material>> = (0..201).map(|_| Arc::new(Lambertian::new(Vec3::vec3(0.5, 0.5, 0.5))) as Arc<dyn Material>).collect();
+        let list = random_objects(3, &materials, with_plane);
+        let bvh = FlatBvh::flat_bvh(random_objects(3, &materials, with_plane));
+
+        let mut rng = Rng::with_seed(4);
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let r = Ray::ray(&Vec3::random_range(-12.0, 12.0, &mut rng), &random_unit_vector(&mut rng));
+            let mut expected = HitRecord::default();
+            let mut found = HitRecord::default();
+            let hit = list.hit(&r, Interval::interval(0.001, f64::INFINITY), &mut expected);
+            assert_eq!(bvh.hit(&r, Interval::interval(0.001, f64::INFINITY), &mut found), hit);
+            if hit {
+                hits += 1;
+                assert_eq!(found.t(), expected.t());
+                assert!(std::ptr::addr_eq(found.mat(), expected.mat()));
+            }
+        }
+        assert!(hits > 100, "only {} rays hit anything", hits);
+    }
+
+    #[test]
+    fn hit_matches_linear_scan() {
+        matches_linear_scan(false);
+    }
+
+    #[test]
+    fn hit_matches_linear_scan_with_infinite_plane() {
+        matches_linear_scan(true);
+    }
+}
@@ -0,0 +1,77 @@
+use std::io::Write;
+use std::path::Path;
+use crate::vec3::Vec3;
+use crate::output::{save_image, write_image, ImageFormat};
+use crate::input::load_image;
+
+// linear RGB image, stored row major starting at the top left pixel
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+}
+
+impl Framebuffer {
+    pub fn framebuffer(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Vec3::identity(); width * height],
+        }
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Vec3>) -> Framebuffer {
+        assert_eq!(pixels.len(), width * height, "pixel count does not match the image size");
+        Framebuffer {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Vec3 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: &Vec3) {
+        self.pixels[y * self.width + x] = *color;
+    }
+
+    pub fn pixels(&self) -> &[Vec3] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Vec3] {
+        &mut self.pixels
+    }
+
+    pub fn row(&self, y: usize) -> &[Vec3] {
+        &self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    // interleaved single precision RGB, e.g. for uploading to a texture
+    pub fn to_rgb_f32(&self) -> Vec<f32> {
+        self.pixels.iter().flat_map(|c| [c.x() as f32, c.y() as f32, c.z() as f32]).collect()
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W, format: ImageFormat) -> std::io::Result<()> {
+        write_image(out, format, self)
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<Framebuffer> {
+        load_image(path)
+    }
+
+    pub fn save(&self, path: &Path, format: ImageFormat) -> std::io::Result<()> {
+        save_image(path, format, self)
+    }
+}
@@ -1,29 +1,43 @@
+use std::sync::Arc;
 use crate::ray::Ray;
 use crate::vec3::{Vec3, dot};
 use crate::interval::Interval;
-use crate::material::*;
+use crate::material::{Absorbing, Material};
+use crate::aabb::AABB;
+use crate::utility::Rng;
 
-pub struct HitRecord {
+// the material is borrowed from the object that was hit, for as long as the object lives
+pub struct HitRecord<'a> {
     p: Vec3,
     normal: Vec3,
-    mat: Materials,
+    mat: Option<&'a dyn Material>,
     t: f64,
+    u: f64,
+    v: f64,
+    dpdu: Vec3,
+    dpdv: Vec3,
     front_face: bool
 }
 
-impl HitRecord {
-    pub fn default() -> HitRecord {
+impl Default for HitRecord<'_> {
+    fn default() -> Self {
         HitRecord {
             p: Vec3::vec3(0.0, 0.0, 0.0),
             normal: Vec3::vec3(0.0, 0.0, 0.0),
-            mat: Materials::default(),
+            mat: None,
             t: 0.0,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::vec3(0.0, 0.0, 0.0),
+            dpdv: Vec3::vec3(0.0, 0.0, 0.0),
             front_face: false,
         }
     }
+}
 
+impl<'a> HitRecord<'a> {
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: &Vec3) {
-        self.front_face = dot(&r.direction(), &outward_normal) < 0.0;
+        self.front_face = dot(&r.direction(), outward_normal) < 0.0;
         if self.front_face {
             self.normal = *outward_normal;
         } else {
@@ -43,8 +57,30 @@ impl HitRecord {
        self.t 
     }
 
-    pub fn mat(&self) -> Materials {
-       self.mat
+    pub fn mat(&self) -> &dyn Material {
+        match &self.mat {
+            Some(mat) => *mat,
+            None => &Absorbing,
+        }
+    }
+
+    // surface coordinates of the hit point
+    pub fn u(&self) -> f64 {
+        self.u
+    }
+
+    pub fn v(&self) -> f64 {
+        self.v
+    }
+
+    // partial derivatives of the hit point with respect to u and v, they are tangent
+    // to the surface and not normalized
+    pub fn dpdu(&self) -> Vec3 {
+        self.dpdu
+    }
+
+    pub fn dpdv(&self) -> Vec3 {
+        self.dpdv
     }
 
     pub fn front_face(&self) -> bool {
@@ -55,8 +91,18 @@ impl HitRecord {
         self.front_face = *front;
     }
 
-    pub fn set_mat(&mut self, mat: &Materials) {
-        self.mat = *mat;
+    pub fn set_mat(&mut self, mat: &'a Arc<dyn Material>) {
+        self.mat = Some(mat.as_ref());
+    }
+
+    pub fn set_uv(&mut self, u: &f64, v: &f64) {
+        self.u = *u;
+        self.v = *v;
+    }
+
+    pub fn set_tangents(&mut self, dpdu: &Vec3, dpdv: &Vec3) {
+        self.dpdu = *dpdu;
+        self.dpdv = *dpdv;
     }
 
     pub fn set_p(&mut self, p: &Vec3) {
@@ -73,5 +119,56 @@ impl HitRecord {
 }
 
 pub trait Hittable: Sync + Send {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
+    // rec is only written when hit returns true, and then only for a hit inside ray_t.
+    // lists pass their record straight to their children and rely on this to keep the
+    // closest hit, so an object must not touch rec before it knows it was hit
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool;
+    fn bounding_box(&self) -> AABB;
+
+    // density of random(origin) with respect to solid angle, objects that cannot be
+    // sampled return zero and are never used as lights
+    fn pdf_value(&self, _origin: &Vec3, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    // direction from origin towards a random point on the object
+    fn random(&self, _origin: &Vec3, _rng: &mut Rng) -> Vec3 {
+        Vec3::vec3(1.0, 0.0, 0.0)
+    }
+}
+
+impl<T: Hittable + ?Sized> Hittable for Box<T> {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        (**self).hit(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        (**self).bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        (**self).pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Vec3, rng: &mut Rng) -> Vec3 {
+        (**self).random(origin, rng)
+    }
+}
+
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        (**self).hit(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        (**self).bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        (**self).pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Vec3, rng: &mut Rng) -> Vec3 {
+        (**self).random(origin, rng)
+    }
 }
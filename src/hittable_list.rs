@@ -1,43 +1,75 @@
 use crate::hittable::*;
-use crate::vec3::*;
 use crate::ray::Ray;
 use crate::interval::Interval;
-use crate::material::*;
+use crate::aabb::AABB;
+use crate::vec3::Vec3;
+use crate::utility::Rng;
 
 pub struct HittableList {
     objects: Vec<Box<dyn Hittable>>,
+    bbox: AABB,
 }
 
 impl HittableList {
     pub fn hittable_list() -> HittableList {
         HittableList {
-            objects: Vec::new()
+            objects: Vec::new(),
+            bbox: AABB::identity(),
         }
     }
 
     pub fn add(&mut self, object: Box<dyn Hittable>) {
+        self.bbox = AABB::aabb_boxes(&self.bbox, &object.bounding_box());
         self.objects.push(object);
     }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn into_objects(self) -> Vec<Box<dyn Hittable>> {
+        self.objects
+    }
 }
 
 impl Hittable for HittableList {
-     fn  hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
-        let mut temp_rec = HitRecord::default();
+     fn  hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
         let mut hit_anything = false;
         let mut closest_so_far = ray_t.max();
 
+        // objects only write to the record when they report a hit, and the shrinking
+        // interval guarantees it is closer than the previous one
         for object in self.objects.iter() {
-            if object.hit(&r, Interval::interval(ray_t.min(), closest_so_far), &mut temp_rec) {
+            if object.hit(r, Interval::interval(ray_t.min(), closest_so_far), rec) {
                 hit_anything = true;
-                closest_so_far = temp_rec.t();
-                rec.set_p(&temp_rec.p());
-                rec.set_t(&temp_rec.t());
-                rec.set_normal(&temp_rec.normal());
-                rec.set_mat(&temp_rec.mat());
-                rec.set_front_face(&temp_rec.front_face());
+                closest_so_far = rec.t();
             }
         }
 
         return hit_anything;
      }
+
+     fn bounding_box(&self) -> AABB {
+         self.bbox
+     }
+
+     // every object is picked with the same probability
+     fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+         if self.objects.is_empty() {
+             return 0.0;
+         }
+         let weight = 1.0 / self.objects.len() as f64;
+         self.objects.iter().map(|object| weight * object.pdf_value(origin, direction)).sum()
+     }
+
+     fn random(&self, origin: &Vec3, rng: &mut Rng) -> Vec3 {
+         if self.objects.is_empty() {
+             return Vec3::vec3(1.0, 0.0, 0.0);
+         }
+         self.objects[rng.usize(..self.objects.len())].random(origin, rng)
+     }
 }
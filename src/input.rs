@@ -0,0 +1,109 @@
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Read};
+use std::path::Path;
+use crate::color::gamma_to_linear;
+use crate::framebuffer::Framebuffer;
+use crate::vec3::Vec3;
+
+// decodes a PNG or PPM (P3/P6) file into linear colors, the format is picked from the file contents
+pub fn load_image(path: &Path) -> std::io::Result<Framebuffer> {
+    let mut data = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut data)?;
+
+    if data.starts_with(b"\x89PNG") {
+        return read_png(&data);
+    }
+    if data.starts_with(b"P3") || data.starts_with(b"P6") {
+        return read_ppm(&data);
+    }
+    Err(Error::new(ErrorKind::InvalidData, format!("{}: unsupported image format, expected PNG or PPM", path.display())))
+}
+
+fn read_png(data: &[u8]) -> std::io::Result<Framebuffer> {
+    let mut decoder = png::Decoder::new(data);
+    // palettes and low bit depths are expanded, 16-bit channels are cut down to 8
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(Error::other)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(Error::other)?;
+    let bytes = &buf[..info.buffer_size()];
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => return Err(Error::new(ErrorKind::InvalidData, "unexpanded palette image")),
+    };
+
+    let to_linear = |c: u8| gamma_to_linear(c as f64 / 255.0);
+    let pixels = bytes.chunks_exact(channels).map(|px| {
+        if channels < 3 {
+            let g = to_linear(px[0]);
+            Vec3::vec3(g, g, g)
+        } else {
+            Vec3::vec3(to_linear(px[0]), to_linear(px[1]), to_linear(px[2]))
+        }
+    }).collect();
+
+    Ok(Framebuffer::from_pixels(info.width as usize, info.height as usize, pixels))
+}
+
+fn read_ppm(data: &[u8]) -> std::io::Result<Framebuffer> {
+    let invalid = |message: &str| Error::new(ErrorKind::InvalidData, format!("invalid ppm: {}", message));
+
+    // header: magic, width, height and maximum value, separated by whitespace and comments
+    let mut pos = 0;
+    let mut fields = Vec::new();
+    while fields.len() < 4 {
+        while pos < data.len() && (data[pos].is_ascii_whitespace() || data[pos] == b'#') {
+            if data[pos] == b'#' {
+                while pos < data.len() && data[pos] != b'\n' {
+                    pos += 1;
+                }
+            } else {
+                pos += 1;
+            }
+        }
+        let start = pos;
+        while pos < data.len() && !data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err(invalid("truncated header"));
+        }
+        fields.push(String::from_utf8_lossy(&data[start..pos]).to_string());
+    }
+    // exactly one whitespace byte separates the header from binary data
+    pos += 1;
+
+    let number = |text: &str| text.parse::<usize>().map_err(|_| invalid("bad header value"));
+    let width = number(&fields[1])?;
+    let height = number(&fields[2])?;
+    let max_value = number(&fields[3])?;
+    if max_value == 0 || max_value > 65535 {
+        return Err(invalid("maximum value out of range"));
+    }
+
+    // the header is not trusted, its dimensions could overflow
+    let count = width.checked_mul(height).and_then(|n| n.checked_mul(3)).ok_or(invalid("image dimensions too large"))?;
+    let samples: Vec<usize> = if fields[0] == "P6" {
+        let body = data.get(pos..).unwrap_or(&[]);
+        if max_value < 256 {
+            body.iter().take(count).map(|b| *b as usize).collect()
+        } else {
+            body.chunks_exact(2).take(count).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize).collect()
+        }
+    } else {
+        let body = String::from_utf8_lossy(data.get(pos..).unwrap_or(&[])).to_string();
+        body.split_whitespace().take(count).map(number).collect::<std::io::Result<_>>()?
+    };
+    if samples.len() < count {
+        return Err(invalid("truncated pixel data"));
+    }
+
+    let to_linear = |c: usize| gamma_to_linear(c as f64 / max_value as f64);
+    let pixels = samples.chunks_exact(3).map(|px| Vec3::vec3(to_linear(px[0]), to_linear(px[1]), to_linear(px[2]))).collect();
+
+    Ok(Framebuffer::from_pixels(width, height, pixels))
+}
@@ -1,4 +1,5 @@
 
+#[derive(Copy, Clone)]
 pub struct Interval {
     min: f64,
     max: f64
@@ -12,6 +13,13 @@ impl Interval {
         }
     }
 
+    pub fn interval_union(a: &Interval, b: &Interval) -> Interval {
+        Self {
+            min: f64::min(a.min, b.min),
+            max: f64::max(a.max, b.max),
+        }
+    }
+
     pub fn identity() -> Interval {
         Self {
             min: f64::INFINITY,
@@ -0,0 +1,63 @@
+// naming follows the book, e.g. `Vec3::vec3` and `Textures::SOLID_COLOR`
+#![allow(clippy::self_named_constructors, clippy::upper_case_acronyms, clippy::needless_return)]
+
+pub mod vec3;
+pub mod color;
+pub mod ray;
+pub mod hittable;
+pub mod sphere;
+pub mod hittable_list;
+pub mod interval;
+pub mod camera;
+pub mod utility;
+pub mod material;
+pub mod aabb;
+pub mod output;
+pub mod framebuffer;
+pub mod bvh;
+pub mod flat_bvh;
+pub mod scene;
+pub mod texture;
+pub mod perlin;
+pub mod input;
+pub mod onb;
+pub mod quad;
+pub mod triangle;
+pub mod disk;
+pub mod plane;
+pub mod mesh;
+pub mod obj;
+pub mod transform;
+pub mod constant_medium;
+pub mod pdf;
+pub mod microfacet;
+pub mod sampler;
+pub mod spectrum;
+
+pub use crate::aabb::AABB;
+pub use crate::bvh::BvhNode;
+pub use crate::camera::{Background, Camera};
+pub use crate::constant_medium::ConstantMedium;
+pub use crate::disk::Disk;
+pub use crate::flat_bvh::{BvhStats, FlatBvh};
+pub use crate::framebuffer::Framebuffer;
+pub use crate::hittable::{HitRecord, Hittable};
+pub use crate::hittable_list::HittableList;
+pub use crate::interval::Interval;
+pub use crate::material::{Conductor, ConductorFresnel, Dielectric, DiffuseLight, Dispersion, Isotropic, Lambertian, Material, Metal, Principled, RoughDielectric, ScatterRecord};
+pub use crate::mesh::{MeshFace, TriangleMesh};
+pub use crate::microfacet::Ggx;
+pub use crate::obj::{load_obj, parse_obj_in};
+pub use crate::onb::Onb;
+pub use crate::pdf::{CosinePdf, GgxPdf, HittablePdf, Pdf, Pdfs, PrincipledPdf, SpherePdf};
+pub use crate::output::ImageFormat;
+pub use crate::plane::Plane;
+pub use crate::quad::{make_box, Quad};
+pub use crate::ray::Ray;
+pub use crate::sampler::{PixelSampler, SamplerKind};
+pub use crate::scene::{load_scene, parse_scene, parse_scene_in, Scene, SceneError};
+pub use crate::sphere::Sphere;
+pub use crate::texture::{CheckerTexture, ImageTexture, NoiseKind, NoiseTexture, SolidColor, Texture, Textures};
+pub use crate::transform::{Mat4, Rotate, RotateX, RotateY, RotateZ, Transform, Translate};
+pub use crate::triangle::Triangle;
+pub use crate::vec3::Vec3;
@@ -1,65 +1,125 @@
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use crate::vec3::*;
-use crate::material::*;
-
-mod vec3;
-mod color;
-mod ray;
-mod hittable;
-mod sphere;
-mod hittable_list;
-mod interval;
-mod camera;
-mod utility;
-mod material;
-mod aabb;
-
-use crate::camera::Camera;
-use crate::hittable_list::HittableList;
-use crate::sphere::Sphere;
-use crate::utility::*;
+#![allow(clippy::needless_return)]
+
+use std::sync::Arc;
+use raytracing::vec3::*;
+use raytracing::material::*;
+
+mod cli;
+
+use raytracing::camera::Camera;
+use raytracing::hittable_list::HittableList;
+use raytracing::flat_bvh::FlatBvh;
+use raytracing::sphere::Sphere;
+use raytracing::utility::*;
+use raytracing::scene::load_scene;
+use crate::cli::{parse_args, Command, USAGE};
 
 fn main() { 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if !options.overwrite() && options.output().exists() {
+        eprintln!("error: {} already exists, pass --output or --force to replace it", options.output().display());
+        std::process::exit(1);
+    }
+
+    if let Some(threads) = options.threads() {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .expect("Failed to Create the Render Threads");
+    }
+
+    // the scene file is optional, otherwise the built in scene is rendered
+    let (mut cam, world, lights) = match options.scene() {
+        Some(path) => match load_scene(path) {
+            Ok(scene) => scene.into_parts(),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => {
+            let mut rng = match options.seed() {
+                Some(seed) => Rng::with_seed(seed),
+                None => Rng::new(),
+            };
+            let (cam, world) = random_spheres(&mut rng);
+            (cam, world, HittableList::hittable_list())
+        }
+    };
+    options.apply_overrides(&mut cam);
+
+    let world = FlatBvh::flat_bvh(world);
+    println!("{}", world.stats());
+
+    let image = cam.render(&world, &lights);
+    if let Some(average) = cam.average_samples().filter(|_| cam.noise_threshold() > 0.0) {
+        println!("adaptive sampling: {:.1} samples per pixel on average", average);
+    }
+    if let Err(e) = image.save(options.output(), options.format()) {
+        eprintln!("error: failed to write {}: {}", options.output().display(), e);
+        std::process::exit(1);
+    }
+
+    if let (Some((path, format)), Some(heatmap)) = (options.heatmap(), cam.sample_heatmap()) {
+        if let Err(e) = heatmap.save(path, *format) {
+            eprintln!("error: failed to write {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn random_spheres(rng: &mut Rng) -> (Camera, HittableList) {
     // world
     let mut world = HittableList::hittable_list();
     
-    let ground_material = Materials::METAL(Metal::new(Vec3::vec3(0.3, 0.3, 0.3), 0.0001));
+    let ground_material = Arc::new(Metal::new(Vec3::vec3(0.3, 0.3, 0.3), 0.0001));
     world.add(Box::new(Sphere::sphere(Vec3::vec3(0.0, -1000.0, 0.0), 1000.0, ground_material)));
 
 
     for a in -3..3 {
         for b in -3..3 {
-            let choose_mat = random_double();
-            let center = Vec3::vec3(a as f64 + 0.9 * random_double(), 0.2, b as f64 + 0.9 * random_double());
+            let choose_mat = random_double(rng);
+            let center = Vec3::vec3(a as f64 + 0.9 * random_double(rng), 0.2, b as f64 + 0.9 * random_double(rng));
 
             if choose_mat < 0.8 {
                 // diffuse
-                let albedo = Vec3::random() * Vec3::random();
-                let sphere_material = Materials::LAMBERTIAN(Lambertian::new(albedo));
-                let center2 = center + Vec3::vec3(0.0, random_double_range(0.0, 0.5), 0.0);
+                let albedo = Vec3::random(rng) * Vec3::random(rng);
+                let sphere_material = Arc::new(Lambertian::new(albedo));
+                let center2 = center + Vec3::vec3(0.0, random_double_range(0.0, 0.5, rng), 0.0);
                 world.add(Box::new(Sphere::sphere_moving(center, center2, 0.2, sphere_material)));
             } else if choose_mat < 0.95 {
                 // metal
-                let albedo = Vec3::random_range(0.5, 1.0);
-                let fuzz = random_double_range(0.0, 0.5);
-                let sphere_material = Materials::METAL(Metal::new(albedo, fuzz));
+                let albedo = Vec3::random_range(0.5, 1.0, rng);
+                let fuzz = random_double_range(0.0, 0.5, rng);
+                let sphere_material = Arc::new(Metal::new(albedo, fuzz));
                 world.add(Box::new(Sphere::sphere(center, 0.2, sphere_material)));
             } else {
                 // glass
-                let sphere_material = Materials::DIELECTRIC(Dielectric::new(1.5));
+                let sphere_material = Arc::new(Dielectric::new(1.5));
                 world.add(Box::new(Sphere::sphere(center, 0.2, sphere_material)));
             }
         }
     }
 
-    let material1 = Materials::DIELECTRIC(Dielectric::new(1.5));
+    let material1 = Arc::new(Dielectric::new(1.5));
     world.add(Box::new(Sphere::sphere(Vec3::vec3(0.0, 1.0, 0.0), 1.0, material1)));
 
-    let material2 = Materials::LAMBERTIAN(Lambertian::new(Vec3::vec3(-4.0, 1.0, 0.0)));
+    let material2 = Arc::new(Lambertian::new(Vec3::vec3(-4.0, 1.0, 0.0)));
     world.add(Box::new(Sphere::sphere(Vec3::vec3(-4.0, 1.0, 0.0), 1.0, material2)));
 
-    let material3 = Materials::METAL(Metal::new(Vec3::vec3(0.2, 0.2, 0.5), 0.01));
+    let material3 = Arc::new(Metal::new(Vec3::vec3(0.2, 0.2, 0.5), 0.01));
     world.add(Box::new(Sphere::sphere(Vec3::vec3(4.0, 1.0, 0.0), 1.0, material3)));
 
     // camera
@@ -74,5 +134,6 @@ fn main() {
     cam.set_v_up(&Vec3::vec3(0.0, 1.0, 0.0));
     cam.set_defocus_angle(&0.6);
     cam.set_focus_dist(&10.0);
-    cam.render(&world).expect("Failed to Render the Image to File");
+
+    return (cam, world);
 }
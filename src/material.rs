@@ -2,69 +2,163 @@ use crate::ray::Ray;
 use crate::hittable::HitRecord;
 use crate::vec3::*;
 use crate::utility::*;
+use crate::texture::*;
+use crate::pdf::*;
+use crate::onb::Onb;
+use crate::microfacet::*;
+use crate::color::luminance;
+use crate::spectrum::WAVELENGTH_D_LINE;
 
-pub trait Material {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+// result of a scatter: either a specular ray that is followed as is, or a distribution
+// the renderer samples directions from and weights with scattering_pdf
+pub struct ScatterRecord {
+    attenuation: Vec3,
+    pdf: Pdfs,
+    skip_pdf: bool,
+    skip_pdf_ray: Ray,
+}
+
+impl Default for ScatterRecord {
+    fn default() -> ScatterRecord {
+        ScatterRecord {
+            attenuation: Vec3::identity(),
+            pdf: Pdfs::default(),
+            skip_pdf: false,
+            skip_pdf_ray: Ray::ray(&Vec3::identity(), &Vec3::identity()),
+        }
+    }
+}
+
+impl ScatterRecord {
+    pub fn attenuation(&self) -> Vec3 {
+        self.attenuation
+    }
+
+    pub fn pdf(&self) -> &Pdfs {
+        &self.pdf
+    }
+
+    pub fn skip_pdf(&self) -> bool {
+        self.skip_pdf
+    }
+
+    pub fn skip_pdf_ray(&self) -> &Ray {
+        &self.skip_pdf_ray
+    }
+
+    pub fn set_attenuation(&mut self, attenuation: &Vec3) {
+        self.attenuation = *attenuation;
+    }
+
+    pub fn set_pdf(&mut self, pdf: &Pdfs) {
+        self.pdf = *pdf;
+        self.skip_pdf = false;
+    }
+
+    pub fn set_specular(&mut self, ray: &Ray) {
+        self.skip_pdf_ray = *ray;
+        self.skip_pdf = true;
+    }
+}
+
+// shapes share their material through an Arc<dyn Material>, anything implementing this
+// can be rendered. only scatter is needed, the defaults absorb everything and emit nothing
+pub trait Material: Send + Sync {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _srec: &mut ScatterRecord, _rng: &mut Rng) -> bool {
+        false
+    }
+
+    // a direction to scatter into when the record asks for one to be sampled, materials
+    // with a distribution that is not one of Pdfs draw it here
+    fn generate(&self, _r_in: &Ray, _rec: &HitRecord, srec: &ScatterRecord, rng: &mut Rng) -> Vec3 {
+        srec.pdf().generate(rng)
+    }
+
+    // density of generate choosing direction, with respect to solid angle
+    fn pdf_value(&self, _r_in: &Ray, _rec: &HitRecord, srec: &ScatterRecord, direction: &Vec3) -> f64 {
+        srec.pdf().value(direction)
+    }
+
+    // density of scattering into the direction of scattered, used with the pdf of the record
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
+    // the fraction of light arriving along scattered that leaves back along r_in, cosine
+    // included, for materials whose color depends on the direction
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, srec: &ScatterRecord, scattered: &Ray) -> Vec3 {
+        srec.attenuation() * self.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Vec3 {
+        Vec3::vec3(0.0, 0.0, 0.0)
+    }
+
+    // shapes with an emissive material are sampled as lights by the scene files
+    fn is_emissive(&self) -> bool {
         false
     }
 }
 
-#[derive(Copy, Clone)]
+// what a hit record holds before a shape sets its material, it absorbs everything
+pub(crate) struct Absorbing;
+
+impl Material for Absorbing {}
+
+#[derive(Clone)]
 pub struct Lambertian {
-    albedo: Vec3,
+    albedo: Textures,
 }
 
 impl Lambertian {
     pub fn new(a: Vec3) -> Lambertian {
+        Lambertian::from_texture(Textures::from(a))
+    }
+
+    pub fn from_texture(tex: Textures) -> Lambertian {
         Self {
-            albedo: a,
+            albedo: tex,
         }
     }
 
-    pub fn get_albedo(&self) -> Vec3 {
-        self.albedo
+    pub fn get_albedo(&self) -> &Textures {
+        &self.albedo
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
-        let mut scatter_direction = rec.normal() + random_unit_vector();
-        
-        // catch degenerate scatter direction
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal();
-        }
-
-        let _scattered = Ray::ray_time(&rec.p(), &scatter_direction, &r_in.time());
-        scattered.set_origin(&_scattered.origin());
-        scattered.set_direction(&_scattered.direction());
-
-        let _attenuation = self.get_albedo();
-        attenuation.set_x(&_attenuation.x());
-        attenuation.set_y(&_attenuation.y());
-        attenuation.set_z(&_attenuation.z());
-        //println!("{:?}", attenuation);
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, _rng: &mut Rng) -> bool {
+        srec.set_attenuation(&self.get_albedo().value(rec.u(), rec.v(), &rec.p()));
+        srec.set_pdf(&Pdfs::COSINE(CosinePdf::cosine_pdf(&rec.normal())));
         return true;
     }
 
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = dot(&rec.normal(), &unit_vector(scattered.direction()));
+        if cos_theta < 0.0 { 0.0 } else { cos_theta / std::f64::consts::PI }
+    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct Metal {
-    albedo: Vec3,
+    albedo: Textures,
     fuzz: f64
 }
 
 impl Metal {
     pub fn new(a: Vec3, f: f64) -> Metal {
+        Metal::from_texture(Textures::from(a), f)
+    }
+
+    pub fn from_texture(tex: Textures, f: f64) -> Metal {
         Self {
-            albedo: a,
+            albedo: tex,
             fuzz: f,
         }
     }
 
-    fn get_albedo(&self) -> Vec3 {
-        self.albedo
+    fn get_albedo(&self) -> &Textures {
+        &self.albedo
     }
 
     fn get_fuzz(&self) -> f64 {
@@ -73,35 +167,94 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
-        let reflected = reflect(&unit_vector(r_in.direction()), &rec.normal());  
-
-        let _scattered = Ray::ray_time(&rec.p(), &(reflected + self.get_fuzz() * random_unit_vector()), &r_in.time());
-        scattered.set_origin(&_scattered.origin());
-        scattered.set_direction(&_scattered.direction());
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, rng: &mut Rng) -> bool {
+        let reflected = reflect(&unit_vector(r_in.direction()), &rec.normal());
+        let direction = reflected + self.get_fuzz() * random_unit_vector(rng);
 
-        let _attenuation = self.get_albedo();
-        attenuation.set_x(&_attenuation.x());
-        attenuation.set_y(&_attenuation.y());
-        attenuation.set_z(&_attenuation.z());
+        srec.set_attenuation(&self.get_albedo().value(rec.u(), rec.v(), &rec.p()));
+        srec.set_specular(&Ray::ray_time(&rec.p(), &direction, &r_in.time()));
         return true;
     }
 }
 
+// how the index of refraction of glass changes with the wavelength, the coefficients are
+// for wavelengths in micrometers as they are usually published
+#[derive(Copy, Clone, Debug)]
+pub enum Dispersion {
+    // the same index at every wavelength
+    Constant(f64),
+    // n = a + b / l^2
+    Cauchy(f64, f64),
+    // n^2 = 1 + sum of b l^2 / (l^2 - c) over three terms
+    Sellmeier([f64; 3], [f64; 3]),
+}
+
+impl Dispersion {
+    // the index of refraction at a wavelength in nanometers
+    pub fn ior(&self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.0).powi(2);
+        match self {
+            Dispersion::Constant(ir) => *ir,
+            Dispersion::Cauchy(a, b) => a + b / l2,
+            Dispersion::Sellmeier(b, c) => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>();
+                f64::sqrt(f64::max(n2, 1.0))
+            }
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Dielectric {
-    ir: f64
+    dispersion: Dispersion,
+    // how much of each color channel the inside absorbs per unit of distance
+    absorption: Vec3,
 }
 
 impl Dielectric {
     pub fn new(_ir: f64) -> Dielectric {
+        Dielectric::from_dispersion(Dispersion::Constant(_ir))
+    }
+
+    pub fn from_dispersion(dispersion: Dispersion) -> Dielectric {
         Self {
-            ir: _ir
+            dispersion,
+            absorption: Vec3::vec3(0.0, 0.0, 0.0),
         }
     }
-    
+
+    // glasses and gems by their Sellmeier coefficients
+    pub fn from_name(name: &str) -> Option<Dielectric> {
+        let (b, c) = match name {
+            "bk7" | "crown" => ([1.03961212, 0.231792344, 1.01046945], [0.00600069867, 0.0200179144, 103.560653]),
+            "fused_silica" => ([0.6961663, 0.4079426, 0.8974794], [0.00467914826, 0.0135120631, 97.9340025]),
+            "sf11" | "flint" => ([1.73759695, 0.313747346, 1.89878101], [0.013188707, 0.0623068142, 155.23629]),
+            "diamond" => ([0.3306, 4.3356, 0.0], [0.030625, 0.011236, 0.0]),
+            _ => return None,
+        };
+        Some(Dielectric::from_dispersion(Dispersion::Sellmeier(b, c)))
+    }
+
+    // Beer-Lambert absorption, light going a distance d through the glass keeps
+    // exp(-absorption * d) of each channel, so thick glass is darker and more saturated
+    pub fn set_absorption(&mut self, absorption: &Vec3) {
+        self.absorption = *absorption;
+    }
+
+    // the absorption that leaves color of white light after it went distance through
+    pub fn set_tint(&mut self, color: &Vec3, distance: &f64) {
+        let coefficient = |c: f64| -f64::ln(c.clamp(1e-6, 1.0)) / *distance;
+        self.absorption = Vec3::vec3(coefficient(color.x()), coefficient(color.y()), coefficient(color.z()));
+    }
+
+    // the index of refraction at the d line, what glass catalogs quote
     pub fn get_ir(&self) -> f64 {
-        return self.ir;
+        return self.dispersion.ior(WAVELENGTH_D_LINE);
+    }
+
+    // the index of refraction seen by a ray, which only disperses in spectral mode
+    pub fn ir_at(&self, wavelength: Option<f64>) -> f64 {
+        return self.dispersion.ior(wavelength.unwrap_or(WAVELENGTH_D_LINE));
     }
 
     pub fn reflectance(&self, cosine: f64, ref_idx: f64) -> f64 {
@@ -112,43 +265,469 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
-        attenuation.set(&Vec3::vec3(1.0, 1.0, 1.0)); 
-        let refraction_ratio;
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, rng: &mut Rng) -> bool {
+        // hitting the back of the surface, the ray went through the inside to get here
         if rec.front_face() {
-            refraction_ratio = 1.0 / self.get_ir();
+            srec.set_attenuation(&Vec3::vec3(1.0, 1.0, 1.0));
         } else {
-            refraction_ratio = self.get_ir();
+            let distance = rec.t() * r_in.direction().length();
+            let transmittance = |a: f64| f64::exp(-a * distance);
+            srec.set_attenuation(&Vec3::vec3(transmittance(self.absorption.x()), transmittance(self.absorption.y()), transmittance(self.absorption.z())));
         }
+        let ir = self.ir_at(r_in.wavelength());
+        let refraction_ratio = if rec.front_face() {
+            1.0 / ir
+        } else {
+            ir
+        };
         
         let unit_direction = unit_vector(r_in.direction());
         let cos_theta = f64::min(dot(&(unit_direction * Vec3::vec3(-1.0, -1.0, -1.0)), &rec.normal()), 1.0);
         let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction;
-        if cannot_refract || self.reflectance(cos_theta, refraction_ratio) > random_double() {
-            direction = reflect(&unit_direction, &rec.normal());
+        let direction = if cannot_refract || self.reflectance(cos_theta, refraction_ratio) > random_double(rng) {
+            reflect(&unit_direction, &rec.normal())
         } else {
-            direction = refract(unit_direction, rec.normal(), refraction_ratio);
+            refract(unit_direction, rec.normal(), refraction_ratio)
+        };
+
+        srec.set_specular(&Ray::ray_time(&rec.p(), &direction, &r_in.time()));
+        return true;
+    }
+}
+
+// how the reflectance of a conductor changes with the angle
+#[derive(Clone)]
+pub enum ConductorFresnel {
+    // measured complex index of refraction eta + i k, per color channel
+    Complex(Vec3, Vec3),
+    // Schlick's approximation from the color at normal incidence
+    Schlick(Textures),
+}
+
+// rough metal, a GGX microfacet surface
+#[derive(Clone)]
+pub struct Conductor {
+    fresnel: ConductorFresnel,
+    ggx: Ggx,
+}
+
+impl Conductor {
+    pub fn new(eta: Vec3, k: Vec3, roughness: f64) -> Conductor {
+        Self {
+            fresnel: ConductorFresnel::Complex(eta, k),
+            ggx: Ggx::ggx(roughness),
+        }
+    }
+
+    pub fn from_texture(tex: Textures, roughness: f64) -> Conductor {
+        Self {
+            fresnel: ConductorFresnel::Schlick(tex),
+            ggx: Ggx::ggx(roughness),
         }
+    }
+
+    // measured metals, the indices at roughly 650, 550 and 450 nm
+    pub fn from_name(name: &str, roughness: f64) -> Option<Conductor> {
+        let (eta, k) = match name {
+            "gold" => (Vec3::vec3(0.18299, 0.42108, 1.37340), Vec3::vec3(3.42420, 2.34590, 1.77040)),
+            "copper" => (Vec3::vec3(0.27105, 0.67693, 1.31640), Vec3::vec3(3.60920, 2.62480, 2.29210)),
+            "aluminium" | "aluminum" => (Vec3::vec3(1.65746, 0.88069, 0.52122), Vec3::vec3(9.22387, 6.26952, 4.83700)),
+            "silver" => (Vec3::vec3(0.15943, 0.14512, 0.13547), Vec3::vec3(3.92910, 3.19000, 2.38080)),
+            _ => return None,
+        };
+        Some(Conductor::new(eta, k, roughness))
+    }
+
+    fn reflectance(&self, cos_i: f64, rec: &HitRecord) -> Vec3 {
+        match &self.fresnel {
+            ConductorFresnel::Complex(eta, k) => fresnel_conductor(cos_i, eta, k),
+            ConductorFresnel::Schlick(tex) => fresnel_schlick(cos_i, &tex.value(rec.u(), rec.v(), &rec.p())),
+        }
+    }
+}
 
-        let _scattered = Ray::ray_time(&rec.p(), &direction, &r_in.time());
-        scattered.set_origin(&_scattered.origin());
-        scattered.set_direction(&_scattered.direction());
+impl Material for Conductor {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, _rng: &mut Rng) -> bool {
+        let unit_direction = unit_vector(r_in.direction());
+
+        if self.ggx.is_smooth() {
+            let cos_theta = f64::min(dot(&-unit_direction, &rec.normal()), 1.0);
+            let reflected = reflect(&unit_direction, &rec.normal());
+            srec.set_attenuation(&self.reflectance(cos_theta, rec));
+            srec.set_specular(&Ray::ray_time(&rec.p(), &reflected, &r_in.time()));
+            return true;
+        }
+
+        srec.set_attenuation(&Vec3::vec3(1.0, 1.0, 1.0));
+        srec.set_pdf(&Pdfs::GGX(GgxPdf::ggx_pdf(&rec.normal(), &-unit_direction, &self.ggx, None)));
         return true;
     }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, _srec: &ScatterRecord, scattered: &Ray) -> Vec3 {
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        let h = unit_vector(wo + wi);
+        return self.ggx.reflection(&wo, &wi) * self.reflectance(dot(&wo, &h), rec);
+    }
 }
 
+// frosted glass, a GGX microfacet boundary that reflects and refracts
 #[derive(Copy, Clone)]
-pub enum Materials {
-    LAMBERTIAN(Lambertian),
-    METAL(Metal),
-    DIELECTRIC(Dielectric),
+pub struct RoughDielectric {
+    ir: f64,
+    ggx: Ggx,
+}
+
+impl RoughDielectric {
+    pub fn new(_ir: f64, roughness: f64) -> RoughDielectric {
+        Self {
+            ir: _ir,
+            ggx: Ggx::ggx(roughness),
+        }
+    }
+
+    pub fn get_ir(&self) -> f64 {
+        return self.ir;
+    }
+
+    // index on the far side of the boundary over the index on the side of the ray
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face() { self.ir } else { 1.0 / self.ir }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, rng: &mut Rng) -> bool {
+        let unit_direction = unit_vector(r_in.direction());
+        srec.set_attenuation(&Vec3::vec3(1.0, 1.0, 1.0));
+
+        if self.ggx.is_smooth() {
+            let cos_theta = f64::min(dot(&-unit_direction, &rec.normal()), 1.0);
+            let direction = if fresnel_dielectric(cos_theta, self.eta(rec)) > random_double(rng) {
+                reflect(&unit_direction, &rec.normal())
+            } else {
+                refract(unit_direction, rec.normal(), 1.0 / self.eta(rec))
+            };
+            srec.set_specular(&Ray::ray_time(&rec.p(), &direction, &r_in.time()));
+            return true;
+        }
+
+        srec.set_pdf(&Pdfs::GGX(GgxPdf::ggx_pdf(&rec.normal(), &-unit_direction, &self.ggx, Some(self.eta(rec)))));
+        return true;
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, _srec: &ScatterRecord, scattered: &Ray) -> Vec3 {
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        let f = dielectric_bsdf(&self.ggx, &wo, &wi, self.eta(rec));
+        return Vec3::vec3(f, f, f);
+    }
+}
+
+// a Disney style layered material: a diffuse base with sheen, GGX specular reflection that
+// turns into a conductor with metallic, a clearcoat on top, and rough glass blended in
+// with transmission. every parameter can be a texture, scalars read its gray value
+#[derive(Clone)]
+pub struct Principled {
+    base_color: Textures,
+    metallic: Textures,
+    roughness: Textures,
+    specular: Textures,
+    clearcoat: Textures,
+    sheen: Textures,
+    transmission: Textures,
+    ior: f64,
+}
+
+// the parameters of a Principled material at one point of the surface
+struct PrincipledLobes {
+    base_color: Vec3,
+    metallic: f64,
+    specular: Ggx,
+    specular_f0: Vec3,
+    clearcoat: f64,
+    sheen: f64,
+    transmission: f64,
+    diffuse_roughness: f64,
+}
+
+impl Principled {
+    pub fn new(base_color: Vec3) -> Principled {
+        Principled::from_texture(Textures::from(base_color))
+    }
+
+    pub fn from_texture(tex: Textures) -> Principled {
+        Self {
+            base_color: tex,
+            metallic: Textures::from(Vec3::vec3(0.0, 0.0, 0.0)),
+            roughness: Textures::from(Vec3::vec3(0.5, 0.5, 0.5)),
+            specular: Textures::from(Vec3::vec3(0.5, 0.5, 0.5)),
+            clearcoat: Textures::from(Vec3::vec3(0.0, 0.0, 0.0)),
+            sheen: Textures::from(Vec3::vec3(0.0, 0.0, 0.0)),
+            transmission: Textures::from(Vec3::vec3(0.0, 0.0, 0.0)),
+            ior: 1.5,
+        }
+    }
+
+    pub fn set_base_color(&mut self, tex: &Textures) {
+        self.base_color = tex.clone();
+    }
+
+    pub fn set_metallic(&mut self, tex: &Textures) {
+        self.metallic = tex.clone();
+    }
+
+    pub fn set_roughness(&mut self, tex: &Textures) {
+        self.roughness = tex.clone();
+    }
+
+    // reflectance of the dielectric base, 0.5 is the 4% of an index of refraction of 1.5
+    pub fn set_specular(&mut self, tex: &Textures) {
+        self.specular = tex.clone();
+    }
+
+    pub fn set_clearcoat(&mut self, tex: &Textures) {
+        self.clearcoat = tex.clone();
+    }
+
+    pub fn set_sheen(&mut self, tex: &Textures) {
+        self.sheen = tex.clone();
+    }
+
+    pub fn set_transmission(&mut self, tex: &Textures) {
+        self.transmission = tex.clone();
+    }
+
+    pub fn set_ior(&mut self, ior: &f64) {
+        self.ior = *ior;
+    }
+
+    fn lobes(&self, rec: &HitRecord) -> PrincipledLobes {
+        let scalar = |tex: &Textures| {
+            let c = tex.value(rec.u(), rec.v(), &rec.p());
+            ((c.x() + c.y() + c.z()) / 3.0).clamp(0.0, 1.0)
+        };
+        let base_color = self.base_color.value(rec.u(), rec.v(), &rec.p());
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness);
+        let dielectric_f0 = 0.08 * scalar(&self.specular);
+
+        PrincipledLobes {
+            base_color,
+            metallic,
+            specular: Ggx::ggx(roughness),
+            specular_f0: (1.0 - metallic) * Vec3::vec3(dielectric_f0, dielectric_f0, dielectric_f0) + metallic * base_color,
+            clearcoat: scalar(&self.clearcoat),
+            sheen: scalar(&self.sheen),
+            transmission: scalar(&self.transmission),
+            diffuse_roughness: roughness,
+        }
+    }
+
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face() { self.ior } else { 1.0 / self.ior }
+    }
+}
+
+// the clearcoat is a thin glossy varnish with a fixed 4% reflectance
+const CLEARCOAT_ROUGHNESS: f64 = 0.1;
+const CLEARCOAT_F0: f64 = 0.04;
+
+fn schlick_weight(cos: f64) -> f64 {
+    (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+}
+
+impl Material for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, _rng: &mut Rng) -> bool {
+        let lobes = self.lobes(rec);
+        let wo = -unit_vector(r_in.direction());
+        let cos_o = dot(&wo, &rec.normal());
+
+        // lobes are sampled in proportion to roughly how much light they reflect
+        let opaque = 1.0 - (1.0 - lobes.metallic) * lobes.transmission;
+        let weights = [
+            (1.0 - lobes.metallic) * (1.0 - lobes.transmission) * f64::max(luminance(&lobes.base_color), lobes.sheen),
+            opaque * luminance(&fresnel_schlick(cos_o, &lobes.specular_f0)),
+            0.25 * lobes.clearcoat * (CLEARCOAT_F0 + (1.0 - CLEARCOAT_F0) * schlick_weight(cos_o)),
+            (1.0 - lobes.metallic) * lobes.transmission,
+        ];
+
+        let clearcoat = Ggx::ggx(CLEARCOAT_ROUGHNESS);
+        let pdf = PrincipledPdf::principled_pdf(&rec.normal(), &wo, &lobes.specular, &clearcoat, self.eta(rec), weights);
+        srec.set_attenuation(&Vec3::vec3(1.0, 1.0, 1.0));
+        srec.set_pdf(&Pdfs::PRINCIPLED(pdf));
+        return true;
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, _srec: &ScatterRecord, scattered: &Ray) -> Vec3 {
+        let lobes = self.lobes(rec);
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        if wo.z() <= 0.0 {
+            return Vec3::vec3(0.0, 0.0, 0.0);
+        }
+
+        let mut f = Vec3::vec3(0.0, 0.0, 0.0);
+        let white = Vec3::vec3(1.0, 1.0, 1.0);
+
+        if wi.z() > 0.0 {
+            let h = unit_vector(wo + wi);
+            let cos_d = dot(&wi, &h);
+
+            // Burley's diffuse, brighter at grazing angles the rougher the surface is, and
+            // sheen tinted halfway towards the base color
+            let fd90 = 0.5 + 2.0 * lobes.diffuse_roughness * cos_d * cos_d;
+            let retro = (1.0 + (fd90 - 1.0) * schlick_weight(wi.z())) * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z()));
+            let diffuse = (retro / std::f64::consts::PI) * lobes.base_color;
+            let tint = match luminance(&lobes.base_color) {
+                l if l > 0.0 => lobes.base_color / l,
+                _ => white,
+            };
+            let sheen = lobes.sheen * schlick_weight(cos_d) * (0.5 * white + 0.5 * tint);
+            f += (1.0 - lobes.metallic) * (1.0 - lobes.transmission) * wi.z() * (diffuse + sheen);
+
+            let opaque = 1.0 - (1.0 - lobes.metallic) * lobes.transmission;
+            f += opaque * lobes.specular.reflection(&wo, &wi) * fresnel_schlick(dot(&wo, &h), &lobes.specular_f0);
+
+            let clearcoat_fresnel = CLEARCOAT_F0 + (1.0 - CLEARCOAT_F0) * schlick_weight(dot(&wo, &h));
+            f += 0.25 * lobes.clearcoat * Ggx::ggx(CLEARCOAT_ROUGHNESS).reflection(&wo, &wi) * clearcoat_fresnel * white;
+        }
+
+        // light refracted through the glass takes on the base color
+        let glass = (1.0 - lobes.metallic) * lobes.transmission;
+        if glass > 0.0 {
+            let bsdf = glass * dielectric_bsdf(&lobes.specular, &wo, &wi, self.eta(rec));
+            f += if wi.z() < 0.0 { bsdf * lobes.base_color } else { bsdf * white };
+        }
+
+        return f;
+    }
+}
+
+// the incoming ray reversed and the scattered ray, in the frame of the normal
+fn local_directions(r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> (Vec3, Vec3) {
+    let uvw = Onb::onb(&rec.normal());
+    let wo = uvw.to_local(&-unit_vector(r_in.direction()));
+    let wi = uvw.to_local(&unit_vector(scattered.direction()));
+    return (wo, wi);
+}
+
+#[derive(Clone)]
+pub struct DiffuseLight {
+    emit: Textures,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Vec3) -> DiffuseLight {
+        DiffuseLight::from_texture(Textures::from(emit))
+    }
+
+    pub fn from_texture(tex: Textures) -> DiffuseLight {
+        Self {
+            emit: tex,
+        }
+    }
+
+    pub fn get_emit(&self) -> &Textures {
+        &self.emit
+    }
+}
+
+impl Material for DiffuseLight {
+    // lights only emit, the default scatter absorbs every incoming ray
+    fn emitted(&self, _r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        self.get_emit().value(rec.u(), rec.v(), &rec.p())
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
+
+// phase function of participating media, scatters uniformly in all directions
+#[derive(Clone)]
+pub struct Isotropic {
+    albedo: Textures,
 }
 
-impl Default for Materials {
-    fn default() -> Self{Materials::LAMBERTIAN(Lambertian{
-        albedo: Vec3::vec3(0.0, 0.0, 0.0)
-    })}
+impl Isotropic {
+    pub fn new(albedo: Vec3) -> Isotropic {
+        Isotropic::from_texture(Textures::from(albedo))
+    }
+
+    pub fn from_texture(tex: Textures) -> Isotropic {
+        Self {
+            albedo: tex,
+        }
+    }
+
+    pub fn get_albedo(&self) -> &Textures {
+        &self.albedo
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, _rng: &mut Rng) -> bool {
+        srec.set_attenuation(&self.get_albedo().value(rec.u(), rec.v(), &rec.p()));
+        srec.set_pdf(&Pdfs::SPHERE(SpherePdf::sphere_pdf()));
+        return true;
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * std::f64::consts::PI)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::hittable::Hittable;
+    use crate::interval::Interval;
+    use crate::sphere::Sphere;
+
+    // the attenuation of a ray through a glass sphere of radius 1.5, both from outside
+    // where it enters and from the center where it leaves after going the radius
+    fn attenuation(glass: Dielectric) -> (Vec3, Vec3) {
+        let sphere = Sphere::sphere(Vec3::vec3(0.0, 0.0, 0.0), 1.5, Arc::new(glass));
+        let mut rng = Rng::with_seed(1);
+        // the directions are not unit length, the distance is still measured in world units
+        let entering = Ray::ray(&Vec3::vec3(0.0, 0.0, 5.0), &Vec3::vec3(0.0, 0.0, -2.0));
+        let leaving = Ray::ray(&Vec3::vec3(0.0, 0.0, 0.0), &Vec3::vec3(0.0, 2.0, 0.0));
+
+        let mut result = [Vec3::identity(); 2];
+        for (ray, attenuation) in [entering, leaving].iter().zip(result.iter_mut()) {
+            let mut rec = HitRecord::default();
+            assert!(sphere.hit(ray, Interval::interval(0.001, f64::INFINITY), &mut rec));
+            let mut srec = ScatterRecord::default();
+            assert!(rec.mat().scatter(ray, &rec, &mut srec, &mut rng));
+            *attenuation = srec.attenuation();
+        }
+        return (result[0], result[1]);
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn dielectric_absorbs_along_the_distance_inside() {
+        let absorption = Vec3::vec3(0.1, 0.5, 2.0);
+        let mut glass = Dielectric::new(1.5);
+        glass.set_absorption(&absorption);
+
+        let (entering, leaving) = attenuation(glass);
+        assert_close(entering, Vec3::vec3(1.0, 1.0, 1.0));
+        assert_close(leaving, Vec3::vec3(f64::exp(-0.1 * 1.5), f64::exp(-0.5 * 1.5), f64::exp(-2.0 * 1.5)));
+    }
+
+    #[test]
+    fn dielectric_tint_is_reached_at_its_distance() {
+        let tint = Vec3::vec3(0.8, 0.5, 0.2);
+        let mut glass = Dielectric::new(1.5);
+        glass.set_tint(&tint, &1.5);
+
+        let (entering, leaving) = attenuation(glass);
+        assert_close(entering, Vec3::vec3(1.0, 1.0, 1.0));
+        assert_close(leaving, tint);
+    }
 }
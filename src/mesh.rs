@@ -0,0 +1,215 @@
+use std::sync::Arc;
+use crate::ray::Ray;
+use crate::vec3::*;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::*;
+use crate::aabb::AABB;
+use crate::flat_bvh::{BvhStats, FlatBvh};
+
+// one triangle of a mesh, the indices point into the shared buffers of the mesh. faces
+// without normals are shaded flat and faces without uvs use their barycentric coordinates
+#[derive(Copy, Clone, Debug)]
+pub struct MeshFace {
+    positions: [u32; 3],
+    normals: Option<[u32; 3]>,
+    uvs: Option<[u32; 3]>,
+    material: u32,
+}
+
+impl MeshFace {
+    pub fn mesh_face(positions: [u32; 3], normals: Option<[u32; 3]>, uvs: Option<[u32; 3]>, material: u32) -> MeshFace {
+        MeshFace {
+            positions,
+            normals,
+            uvs,
+            material,
+        }
+    }
+
+    pub fn positions(&self) -> [u32; 3] {
+        self.positions
+    }
+
+    pub fn normals(&self) -> Option<[u32; 3]> {
+        self.normals
+    }
+
+    pub fn uvs(&self) -> Option<[u32; 3]> {
+        self.uvs
+    }
+
+    pub fn material(&self) -> u32 {
+        self.material
+    }
+}
+
+struct MeshData {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<MeshFace>,
+    materials: Vec<Arc<dyn Material>>,
+}
+
+// the BVH primitive, a reference to a single face of the shared mesh data
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: u32,
+}
+
+pub struct TriangleMesh {
+    mesh: Arc<MeshData>,
+    bvh: FlatBvh<MeshTriangle>,
+}
+
+impl TriangleMesh {
+    pub fn triangle_mesh(positions: Vec<Vec3>, normals: Vec<Vec3>, uvs: Vec<(f64, f64)>, faces: Vec<MeshFace>, materials: Vec<Arc<dyn Material>>) -> TriangleMesh {
+        for face in faces.iter() {
+            assert!(face.positions.iter().all(|&i| (i as usize) < positions.len()), "face references a missing position");
+            assert!(face.normals.iter().flatten().all(|&i| (i as usize) < normals.len()), "face references a missing normal");
+            assert!(face.uvs.iter().flatten().all(|&i| (i as usize) < uvs.len()), "face references a missing uv");
+            assert!((face.material as usize) < materials.len(), "face references a missing material");
+        }
+
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            faces,
+            materials,
+        });
+        let triangles = (0..mesh.faces.len() as u32).map(|face| MeshTriangle {
+            mesh: Arc::clone(&mesh),
+            face,
+        }).collect();
+
+        TriangleMesh {
+            mesh,
+            bvh: FlatBvh::from_objects(triangles),
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.mesh.faces.len()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.mesh.positions.len()
+    }
+
+    pub fn stats(&self) -> &BvhStats {
+        self.bvh.stats()
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        self.bvh.hit(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bvh.bounding_box()
+    }
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> (&MeshFace, [Vec3; 3]) {
+        let face = &self.mesh.faces[self.face as usize];
+        let [a, b, c] = face.positions;
+        let positions = &self.mesh.positions;
+        (face, [positions[a as usize], positions[b as usize], positions[c as usize]])
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let (face, [p0, p1, p2]) = self.vertices();
+
+        // Moller-Trumbore, b1 and b2 are the barycentric weights of p1 and p2
+        let e1 = p1 - p0;
+        let e2 = p2 - p0;
+        let pvec = cross(&r.direction(), &e2);
+        let det = dot(&e1, &pvec);
+        if det.abs() < 1e-12 {
+            return false;
+        }
+
+        let inv_det = 1.0 / det;
+        let tvec = r.origin() - p0;
+        let b1 = dot(&tvec, &pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return false;
+        }
+
+        let qvec = cross(&tvec, &e1);
+        let b2 = dot(&r.direction(), &qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return false;
+        }
+
+        let t = dot(&e2, &qvec) * inv_det;
+        if !ray_t.contains(t) {
+            return false;
+        }
+        let b0 = 1.0 - b1 - b2;
+
+        // the vertex normals decide which side is outside, the geometric normal
+        // only follows them so the front face test works at the silhouette
+        let mut geometric_normal = unit_vector(cross(&e1, &e2));
+        let mut shading_normal = geometric_normal;
+        if let Some([n0, n1, n2]) = face.normals {
+            let normals = &self.mesh.normals;
+            let n = b0 * normals[n0 as usize] + b1 * normals[n1 as usize] + b2 * normals[n2 as usize];
+            if n.length_squared() > 0.0 {
+                shading_normal = unit_vector(n);
+                if dot(&shading_normal, &geometric_normal) < 0.0 {
+                    geometric_normal = -geometric_normal;
+                }
+            }
+        }
+
+        // without texture coordinates the barycentrics are used, which makes dpdu and dpdv the edges
+        let mut uv = (b1, b2);
+        let mut dpdu = e1;
+        let mut dpdv = e2;
+        if let Some([t0, t1, t2]) = face.uvs {
+            let uvs = &self.mesh.uvs;
+            let (uv0, uv1, uv2) = (uvs[t0 as usize], uvs[t1 as usize], uvs[t2 as usize]);
+            uv = (
+                b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+                b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+            );
+
+            // solve p0 - p2 and p1 - p2 for the derivatives, unless the uv mapping is degenerate
+            let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+            let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+            let uv_det = du02 * dv12 - dv02 * du12;
+            if uv_det.abs() > 1e-12 {
+                let dp02 = p0 - p2;
+                let dp12 = p1 - p2;
+                dpdu = (dv12 * dp02 - dv02 * dp12) / uv_det;
+                dpdv = (du02 * dp12 - du12 * dp02) / uv_det;
+            }
+        }
+
+        rec.set_t(&t);
+        rec.set_p(&r.at(&t));
+        rec.set_mat(&self.mesh.materials[face.material as usize]);
+        rec.set_face_normal(r, &geometric_normal);
+        if rec.front_face() {
+            rec.set_normal(&shading_normal);
+        } else {
+            rec.set_normal(&-shading_normal);
+        }
+        rec.set_uv(&uv.0, &uv.1);
+        rec.set_tangents(&dpdu, &dpdv);
+
+        return true;
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let (_, [p0, p1, p2]) = self.vertices();
+        AABB::aabb_boxes(&AABB::aabb(&p0, &p1), &AABB::aabb(&p2, &p2)).pad_to_minimums()
+    }
+}
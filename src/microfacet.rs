@@ -0,0 +1,180 @@
+use crate::vec3::*;
+
+// the GGX (Trowbridge-Reitz) distribution of microfacet normals with Smith masking, in a
+// local frame where the surface normal is z
+#[derive(Copy, Clone, Debug)]
+pub struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    // roughness is perceptual, alpha its square, clamped so highlights stay finite
+    pub fn ggx(roughness: f64) -> Ggx {
+        Ggx {
+            alpha: f64::max(roughness * roughness, 1e-4),
+        }
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    // surfaces this smooth are treated as perfect mirrors
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < 1e-3
+    }
+
+    // density of microfacets facing along h
+    pub fn d(&self, h: &Vec3) -> f64 {
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+        let a2 = self.alpha * self.alpha;
+        let t = h.z() * h.z() * (a2 - 1.0) + 1.0;
+        return a2 / (std::f64::consts::PI * t * t);
+    }
+
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 <= 0.0 {
+            return f64::INFINITY;
+        }
+        let tan2 = f64::max(0.0, 1.0 - cos2) / cos2;
+        return 0.5 * (-1.0 + f64::sqrt(1.0 + self.alpha * self.alpha * tan2));
+    }
+
+    // fraction of the microfacets visible from w
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // height correlated masking and shadowing
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // reflection off the microfacets without fresnel, cosine included. both directions
+    // point away from the surface
+    pub fn reflection(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let h = unit_vector(*wo + *wi);
+        return self.d(&h) * self.g(wo, wi) / (4.0 * wo.z());
+    }
+
+    // density of the normals visible from wo, what sample_visible_normal draws from
+    pub fn visible_pdf(&self, wo: &Vec3, h: &Vec3) -> f64 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        return self.g1(wo) * f64::max(0.0, dot(wo, h)) * self.d(h) / wo.z();
+    }
+
+    // Heitz's "Sampling the GGX Distribution of Visible Normals", wo above the surface
+    pub fn sample_visible_normal(&self, wo: &Vec3, u1: f64, u2: f64) -> Vec3 {
+        // stretch the view so the distribution becomes a hemisphere
+        let vh = unit_vector(Vec3::vec3(self.alpha * wo.x(), self.alpha * wo.y(), wo.z()));
+        let len_squared = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len_squared > 0.0 {
+            Vec3::vec3(-vh.y(), vh.x(), 0.0) / len_squared.sqrt()
+        } else {
+            Vec3::vec3(1.0, 0.0, 0.0)
+        };
+        let t2 = cross(&vh, &t1);
+
+        // a point on the disk, warped towards the part of it that is visible
+        let r = u1.sqrt();
+        let phi = 2.0 * std::f64::consts::PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * f64::sqrt(1.0 - p1 * p1) + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + f64::sqrt(f64::max(0.0, 1.0 - p1 * p1 - p2 * p2)) * vh;
+
+        // and unstretch the normal
+        return unit_vector(Vec3::vec3(self.alpha * nh.x(), self.alpha * nh.y(), f64::max(1e-6, nh.z())));
+    }
+}
+
+// reflection and refraction at a rough dielectric boundary, cosine included, from Walter
+// et al. "Microfacet Models for Refraction through Rough Surfaces". eta is the index on
+// the side of wi over the one on the side of wo. the 1 / eta^2 radiance scaling is left
+// out of refraction, like Dielectric does
+pub fn dielectric_bsdf(ggx: &Ggx, wo: &Vec3, wi: &Vec3, eta: f64) -> f64 {
+    if wo.z() <= 0.0 || wi.z() == 0.0 {
+        return 0.0;
+    }
+
+    if wi.z() > 0.0 {
+        let h = unit_vector(*wo + *wi);
+        return ggx.reflection(wo, wi) * fresnel_dielectric(dot(wo, &h), eta);
+    }
+
+    let h = refraction_half_vector(wo, wi, eta);
+    let (cos_o, cos_i) = (dot(wo, &h), dot(wi, &h));
+    if cos_o <= 0.0 || cos_i >= 0.0 {
+        return 0.0;
+    }
+    let denom = cos_o + eta * cos_i;
+    let transmitted = 1.0 - fresnel_dielectric(cos_o, eta);
+    return ggx.d(&h) * ggx.g(wo, wi) * transmitted * eta * eta * cos_o * cos_i.abs() / (wo.z() * denom * denom);
+}
+
+// exact reflectance of a dielectric boundary, eta is the index on the far side over the
+// index on the side of the light, cos_i is measured on the side of the light
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        // total internal reflection
+        return 1.0;
+    }
+    let cos_t = f64::sqrt(1.0 - sin2_t);
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    return 0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular);
+}
+
+// exact reflectance of a conductor with the complex index of refraction eta + i k, for
+// each color channel, seen from air
+pub fn fresnel_conductor(cos_i: f64, eta: &Vec3, k: &Vec3) -> Vec3 {
+    let channel = |eta: f64, k: f64| {
+        let cos2 = cos_i.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2_plus_b2 = f64::sqrt(t0 * t0 + 4.0 * eta * eta * k * k);
+        let t1 = a2_plus_b2 + cos2;
+        let a = f64::sqrt(f64::max(0.0, 0.5 * (a2_plus_b2 + t0)));
+        let t2 = 2.0 * cos_i.clamp(0.0, 1.0) * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rp + rs)
+    };
+    return Vec3::vec3(channel(eta.x(), k.x()), channel(eta.y(), k.y()), channel(eta.z(), k.z()));
+}
+
+// Schlick's approximation from the reflectance at normal incidence
+pub fn fresnel_schlick(cos_i: f64, f0: &Vec3) -> Vec3 {
+    let weight = (1.0 - cos_i.clamp(0.0, 1.0)).powi(5);
+    return *f0 + weight * (Vec3::vec3(1.0, 1.0, 1.0) - *f0);
+}
+
+// the half vector of a refraction, facing the side of wo
+pub fn refraction_half_vector(wo: &Vec3, wi: &Vec3, eta: f64) -> Vec3 {
+    let h = unit_vector(*wo + eta * *wi);
+    if h.z() < 0.0 { -h } else { h }
+}
+
+// wo refracted through a microfacet h into a medium eta times denser, None past the
+// critical angle
+pub fn refract_local(wo: &Vec3, h: &Vec3, eta: f64) -> Option<Vec3> {
+    let cos_i = dot(wo, h);
+    let sin2_t = f64::max(0.0, 1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = f64::sqrt(1.0 - sin2_t);
+    return Some(-*wo / eta + (cos_i / eta - cos_t) * *h);
+}
@@ -0,0 +1,471 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::material::*;
+use crate::mesh::{MeshFace, TriangleMesh};
+use crate::scene::{parse_number, SceneError};
+use crate::texture::*;
+use crate::vec3::*;
+
+// loads a Wavefront OBJ file as one triangle mesh. polygons are split into fans, faces get
+// the material of the last `usemtl` from the MTL files named by `mtllib`, and faces without
+// normals are smoothed per smoothing group (flat for `s off`). statements that do not
+// describe polygons, e.g. lines and free form surfaces, are ignored.
+//
+// a given material replaces all materials of the file, the MTL files are not read then
+pub fn load_obj(path: &Path, material: Option<&Arc<dyn Material>>) -> Result<TriangleMesh, SceneError> {
+    let source = read_source(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let mesh = parse_obj_in(&source, base_dir, material).map_err(|e| with_path(e, path))?;
+    if mesh.triangle_count() == 0 {
+        return Err(SceneError::Io {
+            path: path.to_path_buf(),
+            error: std::io::Error::new(std::io::ErrorKind::InvalidData, "the file contains no faces"),
+        });
+    }
+    Ok(mesh)
+}
+
+pub fn parse_obj_in(source: &str, base_dir: &Path, material: Option<&Arc<dyn Material>>) -> Result<TriangleMesh, SceneError> {
+    let mut parser = ObjParser::new(base_dir, material);
+    for_each_statement(source, |tokens| parser.statement(tokens))?;
+    parser.finish()
+}
+
+// what a MTL file can express, mapped onto the built in materials by to_material
+struct MtlDefinition {
+    diffuse: Vec3,
+    diffuse_map: Option<Textures>,
+    specular: Vec3,
+    emission: Vec3,
+    shininess: f64,
+    ior: Option<f64>,
+    dissolve: f64,
+    illum: i32,
+    metallic: Option<f64>,
+    roughness: Option<f64>,
+    sheen: Option<f64>,
+    clearcoat: Option<f64>,
+    metallic_map: Option<Textures>,
+    roughness_map: Option<Textures>,
+}
+
+impl MtlDefinition {
+    fn new() -> MtlDefinition {
+        MtlDefinition {
+            diffuse: Vec3::vec3(0.8, 0.8, 0.8),
+            diffuse_map: None,
+            specular: Vec3::identity(),
+            emission: Vec3::identity(),
+            shininess: 0.0,
+            ior: None,
+            dissolve: 1.0,
+            illum: 2,
+            metallic: None,
+            roughness: None,
+            sheen: None,
+            clearcoat: None,
+            metallic_map: None,
+            roughness_map: None,
+        }
+    }
+
+    fn to_material(&self) -> Arc<dyn Material> {
+        let albedo = match &self.diffuse_map {
+            Some(texture) => texture.clone(),
+            None => Textures::SOLID_COLOR(SolidColor::new(self.diffuse)),
+        };
+
+        if self.emission.length_squared() > 0.0 {
+            return Arc::new(DiffuseLight::new(self.emission));
+        }
+
+        // files written for the PBR extension map onto the principled material
+        let pbr = [self.metallic, self.roughness, self.sheen, self.clearcoat].iter().any(Option::is_some)
+            || self.metallic_map.is_some()
+            || self.roughness_map.is_some();
+        if pbr {
+            let gray = |value: f64| Textures::from(Vec3::vec3(value, value, value));
+            let mut principled = Principled::from_texture(albedo);
+            principled.set_metallic(&self.metallic_map.clone().unwrap_or(gray(self.metallic.unwrap_or(0.0))));
+            principled.set_roughness(&self.roughness_map.clone().unwrap_or(gray(self.roughness.unwrap_or(0.5))));
+            principled.set_sheen(&gray(self.sheen.unwrap_or(0.0)));
+            principled.set_clearcoat(&gray(self.clearcoat.unwrap_or(0.0)));
+            principled.set_transmission(&gray(1.0 - self.dissolve));
+            principled.set_ior(&self.ior.unwrap_or(1.5));
+            return Arc::new(principled);
+        }
+
+        // transparent, or one of the refraction illumination models
+        if self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            return Arc::new(Dielectric::new(self.ior.unwrap_or(1.5)));
+        }
+
+        // classic files describe metals by Ks
+        if matches!(self.illum, 3 | 5) {
+            // the phong exponent as a GGX alpha, roughness is its square root. Ns = 0 is
+            // a completely rough surface
+            let alpha = f64::sqrt(2.0 / (self.shininess + 2.0));
+            return Arc::new(Conductor::from_texture(Textures::from(self.specular), f64::min(alpha.sqrt(), 1.0)));
+        }
+
+        Arc::new(Lambertian::from_texture(albedo))
+    }
+}
+
+struct ObjParser {
+    base_dir: PathBuf,
+    material_override: Option<Arc<dyn Material>>,
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<MeshFace>,
+    // smoothing group of every face, zero means flat shading
+    smoothing_groups: Vec<u32>,
+    library: HashMap<String, Arc<dyn Material>>,
+    materials: Vec<Arc<dyn Material>>,
+    material_indices: HashMap<String, u32>,
+    current_material: Option<u32>,
+    current_group: u32,
+    textures: HashMap<PathBuf, Textures>,
+}
+
+impl ObjParser {
+    fn new(base_dir: &Path, material: Option<&Arc<dyn Material>>) -> ObjParser {
+        ObjParser {
+            base_dir: base_dir.to_path_buf(),
+            material_override: material.cloned(),
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            faces: Vec::new(),
+            smoothing_groups: Vec::new(),
+            library: HashMap::new(),
+            materials: Vec::new(),
+            material_indices: HashMap::new(),
+            current_material: None,
+            // the default is smooth, which is what files without normals usually expect
+            current_group: 1,
+            textures: HashMap::new(),
+        }
+    }
+
+    fn statement(&mut self, tokens: &[&str]) -> Result<(), String> {
+        let args = &tokens[1..];
+        match tokens[0] {
+            "v" => {
+                // an optional w or vertex color may follow
+                expect_at_least("v", args, 3, "a position (x y z)")?;
+                self.positions.push(Vec3::vec3(parse_number(args[0])?, parse_number(args[1])?, parse_number(args[2])?));
+            }
+            "vn" => {
+                expect_at_least("vn", args, 3, "a normal (x y z)")?;
+                self.normals.push(Vec3::vec3(parse_number(args[0])?, parse_number(args[1])?, parse_number(args[2])?));
+            }
+            "vt" => {
+                expect_at_least("vt", args, 1, "texture coordinates (u v)")?;
+                let v = match args.get(1) {
+                    Some(v) => parse_number(v)?,
+                    None => 0.0,
+                };
+                self.uvs.push((parse_number(args[0])?, v));
+            }
+            "f" => self.parse_face(args)?,
+            "s" => {
+                expect_at_least("s", args, 1, "a smoothing group or off")?;
+                self.current_group = match args[0] {
+                    "off" => 0,
+                    group => group.parse().map_err(|_| format!("'{}' is not a smoothing group", group))?,
+                };
+            }
+            "usemtl" => {
+                expect_at_least("usemtl", args, 1, "a material name")?;
+                if self.material_override.is_none() {
+                    let name = args.join(" ");
+                    self.current_material = Some(self.material_index(&name)?);
+                }
+            }
+            "mtllib" => {
+                expect_at_least("mtllib", args, 1, "a file name")?;
+                if self.material_override.is_none() {
+                    for file in args {
+                        self.load_library(&self.base_dir.join(file))?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn parse_face(&mut self, args: &[&str]) -> Result<(), String> {
+        expect_at_least("f", args, 3, "at least three vertices")?;
+
+        let mut vertices = Vec::with_capacity(args.len());
+        for arg in args {
+            let mut parts = arg.split('/');
+            let position = resolve_index(parts.next().unwrap_or(""), self.positions.len(), "position")?;
+            let uv = match parts.next() {
+                Some("") | None => None,
+                Some(index) => Some(resolve_index(index, self.uvs.len(), "texture coordinate")?),
+            };
+            let normal = match parts.next() {
+                Some("") | None => None,
+                Some(index) => Some(resolve_index(index, self.normals.len(), "normal")?),
+            };
+            vertices.push((position, uv, normal));
+        }
+
+        let material = match self.current_material {
+            Some(material) => material,
+            None => self.default_material(),
+        };
+
+        // fan triangulation, only convex polygons come out right
+        for i in 1..vertices.len() - 1 {
+            let corners = [vertices[0], vertices[i], vertices[i + 1]];
+            let positions = corners.map(|c| c.0);
+            let uvs = match corners.map(|c| c.1) {
+                [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+                _ => None,
+            };
+            let normals = match corners.map(|c| c.2) {
+                [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+                _ => None,
+            };
+            self.faces.push(MeshFace::mesh_face(positions, normals, uvs, material));
+            self.smoothing_groups.push(self.current_group);
+        }
+
+        Ok(())
+    }
+
+    fn default_material(&mut self) -> u32 {
+        let name = "";
+        if let Some(&index) = self.material_indices.get(name) {
+            return index;
+        }
+        let material = match &self.material_override {
+            Some(material) => material.clone(),
+            None => MtlDefinition::new().to_material(),
+        };
+        self.add_material(name, material)
+    }
+
+    fn material_index(&mut self, name: &str) -> Result<u32, String> {
+        if let Some(&index) = self.material_indices.get(name) {
+            return Ok(index);
+        }
+        match self.library.get(name) {
+            Some(material) => {
+                let material = material.clone();
+                Ok(self.add_material(name, material))
+            }
+            None => Err(format!("unknown material '{}', it is not defined by any mtllib", name)),
+        }
+    }
+
+    fn add_material(&mut self, name: &str, material: Arc<dyn Material>) -> u32 {
+        let index = self.materials.len() as u32;
+        self.materials.push(material);
+        self.material_indices.insert(name.to_string(), index);
+        index
+    }
+
+    // errors in the library carry their own location, so they are flattened into the message
+    fn load_library(&mut self, path: &Path) -> Result<(), String> {
+        let source = read_source(path).map_err(|e| e.to_string())?;
+        let mut definitions: Vec<(String, MtlDefinition)> = Vec::new();
+
+        for_each_statement(&source, |tokens| {
+            let args = &tokens[1..];
+            if tokens[0] == "newmtl" {
+                expect_at_least("newmtl", args, 1, "a material name")?;
+                definitions.push((args.join(" "), MtlDefinition::new()));
+                return Ok(());
+            }
+
+            let definition = match definitions.last_mut() {
+                Some((_, definition)) => definition,
+                None => return Err(format!("'{}' before the first newmtl", tokens[0])),
+            };
+            match tokens[0] {
+                "Kd" => definition.diffuse = mtl_color("Kd", args)?,
+                "Ks" => definition.specular = mtl_color("Ks", args)?,
+                "Ke" => definition.emission = mtl_color("Ke", args)?,
+                "Ns" => definition.shininess = mtl_number("Ns", args)?,
+                "Ni" => definition.ior = Some(mtl_number("Ni", args)?),
+                "d" => definition.dissolve = mtl_number("d", args)?,
+                "Tr" => definition.dissolve = 1.0 - mtl_number("Tr", args)?,
+                "Pm" => definition.metallic = Some(mtl_number("Pm", args)?),
+                "Pr" => definition.roughness = Some(mtl_number("Pr", args)?),
+                "Ps" => definition.sheen = Some(mtl_number("Ps", args)?),
+                "Pc" => definition.clearcoat = Some(mtl_number("Pc", args)?),
+                "illum" => {
+                    expect_at_least("illum", args, 1, "an illumination model")?;
+                    definition.illum = args[0].parse().map_err(|_| format!("'{}' is not an illumination model", args[0]))?;
+                }
+                // options such as -s come first, the file name is last
+                "map_Kd" | "map_Pm" | "map_Pr" => {
+                    expect_at_least(tokens[0], args, 1, "a file name")?;
+                    let path = path.parent().unwrap_or(Path::new(".")).join(args[args.len() - 1]);
+                    let texture = Some(self.texture(&path)?);
+                    match tokens[0] {
+                        "map_Kd" => definition.diffuse_map = texture,
+                        "map_Pm" => definition.metallic_map = texture,
+                        _ => definition.roughness_map = texture,
+                    }
+                }
+                _ => {}
+            }
+            Ok(())
+        }).map_err(|e| with_path(e, path).to_string())?;
+
+        for (name, definition) in definitions {
+            self.library.insert(name, definition.to_material());
+        }
+        Ok(())
+    }
+
+    fn texture(&mut self, path: &Path) -> Result<Textures, String> {
+        if let Some(texture) = self.textures.get(path) {
+            return Ok(texture.clone());
+        }
+        let image = ImageTexture::load(path).map_err(|e| format!("cannot load image '{}': {}", path.display(), e))?;
+        let texture = Textures::IMAGE(image);
+        self.textures.insert(path.to_path_buf(), texture.clone());
+        Ok(texture)
+    }
+
+    fn finish(mut self) -> Result<TriangleMesh, SceneError> {
+        self.smooth_normals();
+        Ok(TriangleMesh::triangle_mesh(self.positions, self.normals, self.uvs, self.faces, self.materials))
+    }
+
+    // gives every position one area weighted normal per smoothing group it is used in, for
+    // the faces that have no normals in the file
+    fn smooth_normals(&mut self) {
+        let mut generated: HashMap<(u32, u32), u32> = HashMap::new();
+
+        for (face, &group) in self.faces.iter_mut().zip(self.smoothing_groups.iter()) {
+            if face.normals().is_some() || group == 0 {
+                continue;
+            }
+
+            let [a, b, c] = face.positions();
+            let (p0, p1, p2) = (self.positions[a as usize], self.positions[b as usize], self.positions[c as usize]);
+            // the cross product is twice the area, which is the weight
+            let weighted = cross(&(p1 - p0), &(p2 - p0));
+
+            let normals = face.positions().map(|position| {
+                let index = *generated.entry((position, group)).or_insert_with(|| {
+                    self.normals.push(Vec3::identity());
+                    (self.normals.len() - 1) as u32
+                });
+                self.normals[index as usize] += weighted;
+                index
+            });
+            *face = MeshFace::mesh_face(face.positions(), Some(normals), face.uvs(), face.material());
+        }
+
+        for &index in generated.values() {
+            let n = self.normals[index as usize];
+            if n.length_squared() > 0.0 {
+                self.normals[index as usize] = unit_vector(n);
+            }
+        }
+    }
+}
+
+fn read_source(path: &Path) -> Result<String, SceneError> {
+    std::fs::read_to_string(path).map_err(|error| SceneError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+fn with_path(e: SceneError, path: &Path) -> SceneError {
+    match e {
+        SceneError::Parse { path: None, line, text, message } => SceneError::Parse {
+            path: Some(path.to_path_buf()),
+            line,
+            text,
+            message,
+        },
+        e => e,
+    }
+}
+
+// OBJ and MTL share the line structure of the scene format, with `\` continuing a line
+fn for_each_statement(source: &str, mut statement: impl FnMut(&[&str]) -> Result<(), String>) -> Result<(), SceneError> {
+    let mut pending = String::new();
+    let mut first_line = 0;
+
+    for (index, text) in source.lines().enumerate() {
+        if pending.is_empty() {
+            first_line = index + 1;
+        }
+        let content = match text.find('#') {
+            Some(comment) => &text[..comment],
+            None => text,
+        };
+        if let Some(continued) = content.trim_end().strip_suffix('\\') {
+            pending.push_str(continued);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(content);
+
+        let tokens: Vec<&str> = pending.split_whitespace().collect();
+        if !tokens.is_empty() {
+            statement(&tokens).map_err(|message| SceneError::Parse {
+                path: None,
+                line: first_line,
+                text: text.trim_end().to_string(),
+                message,
+            })?;
+        }
+        pending.clear();
+    }
+
+    Ok(())
+}
+
+// OBJ indices start at one, negative ones count back from the last element defined so far
+fn resolve_index(token: &str, count: usize, what: &str) -> Result<u32, String> {
+    let index: i64 = token.parse().map_err(|_| format!("'{}' is not a {} index", token, what))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{} index {} is out of range, {} defined so far", what, index, count));
+    }
+    Ok(resolved as u32)
+}
+
+fn expect_at_least(what: &str, args: &[&str], count: usize, expected: &str) -> Result<(), String> {
+    if args.len() < count {
+        return Err(format!("{} expects {}, found {} value(s)", what, expected, args.len()));
+    }
+    Ok(())
+}
+
+fn mtl_number(what: &str, args: &[&str]) -> Result<f64, String> {
+    expect_at_least(what, args, 1, "a number")?;
+    parse_number(args[0])
+}
+
+// a single value is a gray, spectral and CIE XYZ colors are not supported
+fn mtl_color(what: &str, args: &[&str]) -> Result<Vec3, String> {
+    expect_at_least(what, args, 1, "a color (r g b)")?;
+    if args[0] == "spectral" || args[0] == "xyz" {
+        return Err(format!("{} {} colors are not supported", what, args[0]));
+    }
+    let r = parse_number(args[0])?;
+    if args.len() < 3 {
+        return Ok(Vec3::vec3(r, r, r));
+    }
+    Ok(Vec3::vec3(r, parse_number(args[1])?, parse_number(args[2])?))
+}
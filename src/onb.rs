@@ -0,0 +1,46 @@
+use crate::vec3::*;
+
+// orthonormal basis with w along a given direction
+#[derive(Copy, Clone, Debug)]
+pub struct Onb {
+    axis: [Vec3; 3],
+}
+
+impl Onb {
+    pub fn onb(n: &Vec3) -> Onb {
+        let w = unit_vector(*n);
+        let a = if w.x().abs() > 0.9 {
+            Vec3::vec3(0.0, 1.0, 0.0)
+        } else {
+            Vec3::vec3(1.0, 0.0, 0.0)
+        };
+        let v = unit_vector(cross(&w, &a));
+        let u = cross(&w, &v);
+
+        Onb {
+            axis: [u, v, w],
+        }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.axis[0]
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.axis[1]
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.axis[2]
+    }
+
+    // transform from basis coordinates to world space
+    pub fn transform(&self, v: &Vec3) -> Vec3 {
+        (v.x() * self.axis[0]) + (v.y() * self.axis[1]) + (v.z() * self.axis[2])
+    }
+
+    // transform from world space to basis coordinates
+    pub fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::vec3(dot(v, &self.axis[0]), dot(v, &self.axis[1]), dot(v, &self.axis[2]))
+    }
+}
@@ -0,0 +1,217 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use crate::color::*;
+use crate::vec3::Vec3;
+use crate::framebuffer::Framebuffer;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    // ascii P3 portable pixmap
+    Ppm,
+    // binary P6 portable pixmap
+    PpmBinary,
+    Png,
+    Png16,
+    // linear 32-bit float portable float map
+    Pfm,
+    // linear radiance RGBE
+    Hdr,
+}
+
+impl ImageFormat {
+    pub fn from_extension(extension: &str) -> Option<ImageFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "pfm" => Some(ImageFormat::Pfm),
+            "hdr" => Some(ImageFormat::Hdr),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        ImageFormat::from_extension(path.extension()?.to_str()?)
+    }
+
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" | "p3" => Some(ImageFormat::Ppm),
+            "p6" => Some(ImageFormat::PpmBinary),
+            "png" => Some(ImageFormat::Png),
+            "png16" => Some(ImageFormat::Png16),
+            "pfm" => Some(ImageFormat::Pfm),
+            "hdr" => Some(ImageFormat::Hdr),
+            _ => None,
+        }
+    }
+}
+
+// none of the formats can store an image without pixels
+fn check_not_empty(image: &Framebuffer) -> std::io::Result<()> {
+    if image.width() == 0 || image.height() == 0 {
+        let message = format!("cannot write an empty {}x{} image", image.width(), image.height());
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, message));
+    }
+    Ok(())
+}
+
+pub fn save_image(path: &Path, format: ImageFormat, image: &Framebuffer) -> std::io::Result<()> {
+    // before the file is created, so a failed save leaves nothing behind
+    check_not_empty(image)?;
+    let image_file = File::create(path)?;
+    let mut writer = BufWriter::new(image_file);
+    write_image(&mut writer, format, image)?;
+    writer.flush()
+}
+
+pub fn write_image<W: Write>(out: &mut W, format: ImageFormat, image: &Framebuffer) -> std::io::Result<()> {
+    let width = image.width();
+    let height = image.height();
+    let pixels = image.pixels();
+    check_not_empty(image)?;
+
+    match format {
+        ImageFormat::Ppm => write_ppm(out, width, height, pixels),
+        ImageFormat::PpmBinary => write_ppm_binary(out, width, height, pixels),
+        ImageFormat::Png => write_png(out, width, height, pixels, png::BitDepth::Eight),
+        ImageFormat::Png16 => write_png(out, width, height, pixels, png::BitDepth::Sixteen),
+        ImageFormat::Pfm => write_pfm(out, width, height, pixels),
+        ImageFormat::Hdr => write_hdr(out, width, height, pixels),
+    }
+}
+
+fn write_ppm<W: Write>(out: &mut W, width: usize, height: usize, pixels: &[Vec3]) -> std::io::Result<()> {
+    out.write_all(format!("P3\n{} {}\n255\n", width, height).as_bytes())?;
+    for pixel_color in pixels.iter() {
+        write_color(out, *pixel_color)?;
+    }
+    Ok(())
+}
+
+fn write_ppm_binary<W: Write>(out: &mut W, width: usize, height: usize, pixels: &[Vec3]) -> std::io::Result<()> {
+    out.write_all(format!("P6\n{} {}\n255\n", width, height).as_bytes())?;
+    let data: Vec<u8> = pixels.iter().flat_map(|c| [to_u8(c.x()), to_u8(c.y()), to_u8(c.z())]).collect();
+    out.write_all(&data)
+}
+
+fn write_png<W: Write>(out: &mut W, width: usize, height: usize, pixels: &[Vec3], depth: png::BitDepth) -> std::io::Result<()> {
+    let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(depth);
+
+    let data: Vec<u8> = match depth {
+        png::BitDepth::Sixteen => pixels.iter()
+            .flat_map(|c| [to_u16(c.x()), to_u16(c.y()), to_u16(c.z())])
+            .flat_map(|c| c.to_be_bytes())
+            .collect(),
+        _ => pixels.iter().flat_map(|c| [to_u8(c.x()), to_u8(c.y()), to_u8(c.z())]).collect(),
+    };
+
+    let mut writer = encoder.write_header().map_err(std::io::Error::other)?;
+    writer.write_image_data(&data).map_err(std::io::Error::other)?;
+    writer.finish().map_err(std::io::Error::other)
+}
+
+fn write_pfm<W: Write>(out: &mut W, width: usize, height: usize, pixels: &[Vec3]) -> std::io::Result<()> {
+    // a negative scale marks little endian data, rows are stored bottom to top
+    out.write_all(format!("PF\n{} {}\n-1.0\n", width, height).as_bytes())?;
+    for row in pixels.chunks(width).rev() {
+        for c in row.iter() {
+            out.write_all(&(c.x() as f32).to_le_bytes())?;
+            out.write_all(&(c.y() as f32).to_le_bytes())?;
+            out.write_all(&(c.z() as f32).to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn write_hdr<W: Write>(out: &mut W, width: usize, height: usize, pixels: &[Vec3]) -> std::io::Result<()> {
+    out.write_all(format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width).as_bytes())?;
+    let data: Vec<u8> = pixels.iter().flat_map(|c| to_rgbe(*c)).collect();
+    out.write_all(&data)
+}
+
+// shared exponent encoding, the mantissas are scaled so the largest component lands in [128, 256)
+fn to_rgbe(c: Vec3) -> [u8; 4] {
+    let r = f64::max(c.x(), 0.0);
+    let g = f64::max(c.y(), 0.0);
+    let b = f64::max(c.z(), 0.0);
+    let max = f64::max(r, f64::max(g, b));
+    if !max.is_finite() || max < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    let exponent = max.log2().floor() as i32 + 1;
+    let scale = 256.0 / f64::powi(2.0, exponent);
+    return [
+        f64::min(r * scale, 255.0) as u8,
+        f64::min(g * scale, 255.0) as u8,
+        f64::min(b * scale, 255.0) as u8,
+        (exponent + 128) as u8,
+    ];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_from_extensions_and_names() {
+        assert_eq!(ImageFormat::from_extension("ppm"), Some(ImageFormat::Ppm));
+        assert_eq!(ImageFormat::from_extension("PNG"), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_extension("pfm"), Some(ImageFormat::Pfm));
+        assert_eq!(ImageFormat::from_extension("hdr"), Some(ImageFormat::Hdr));
+        assert_eq!(ImageFormat::from_extension("p6"), None);
+        assert_eq!(ImageFormat::from_extension("jpg"), None);
+        assert_eq!(ImageFormat::from_path(Path::new("renders/out.HDR")), Some(ImageFormat::Hdr));
+        assert_eq!(ImageFormat::from_path(Path::new("renders/out")), None);
+
+        assert_eq!(ImageFormat::from_name("p3"), Some(ImageFormat::Ppm));
+        assert_eq!(ImageFormat::from_name("P6"), Some(ImageFormat::PpmBinary));
+        assert_eq!(ImageFormat::from_name("png16"), Some(ImageFormat::Png16));
+        assert_eq!(ImageFormat::from_name("exr"), None);
+    }
+
+    #[test]
+    fn pfm_is_little_endian_bottom_row_first() {
+        let image = Framebuffer::from_pixels(2, 2, vec![
+            Vec3::vec3(1.0, 2.0, 3.0), Vec3::vec3(4.0, 5.0, 6.0),
+            Vec3::vec3(0.25, 0.5, 0.75), Vec3::vec3(-1.0, 1e6, 0.0),
+        ]);
+        let mut bytes = Vec::new();
+        write_image(&mut bytes, ImageFormat::Pfm, &image).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let values: Vec<f32> = bytes[header.len()..].chunks(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        assert_eq!(values, [0.25, 0.5, 0.75, -1.0, 1e6, 0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn rgbe_shares_the_exponent_of_the_largest_component() {
+        assert_eq!(to_rgbe(Vec3::vec3(1.0, 0.5, 0.25)), [128, 64, 32, 129]);
+        assert_eq!(to_rgbe(Vec3::vec3(0.75, 0.0, 0.0)), [192, 0, 0, 128]);
+        assert_eq!(to_rgbe(Vec3::vec3(0.0, 1000.0, 0.0)), [0, 250, 0, 138]);
+        // negative components are clamped, black and non finite colors are stored as zero
+        assert_eq!(to_rgbe(Vec3::vec3(-1.0, 2.0, 0.0)), [0, 128, 0, 130]);
+        assert_eq!(to_rgbe(Vec3::vec3(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(Vec3::vec3(f64::NAN, 0.0, 0.0)), [0, 0, 0, 0]);
+
+        let image = Framebuffer::from_pixels(3, 1, vec![Vec3::vec3(1.0, 0.5, 0.25); 3]);
+        let mut bytes = Vec::new();
+        write_image(&mut bytes, ImageFormat::Hdr, &image).unwrap();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 3\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(&bytes[header.len()..], [128, 64, 32, 129].repeat(3).as_slice());
+    }
+
+    #[test]
+    fn empty_images_are_an_error() {
+        let image = Framebuffer::from_pixels(0, 4, Vec::new());
+        for format in [ImageFormat::Ppm, ImageFormat::PpmBinary, ImageFormat::Png, ImageFormat::Png16, ImageFormat::Pfm, ImageFormat::Hdr] {
+            let error = write_image(&mut Vec::new(), format, &image).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+}
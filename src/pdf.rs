@@ -0,0 +1,271 @@
+use crate::vec3::*;
+use crate::hittable::Hittable;
+use crate::onb::Onb;
+use crate::microfacet::*;
+use crate::utility::*;
+
+// a distribution of directions, value is the density with respect to solid angle
+pub trait Pdf {
+    fn value(&self, direction: &Vec3) -> f64;
+    fn generate(&self, rng: &mut Rng) -> Vec3;
+}
+
+// uniform over all directions
+#[derive(Copy, Clone, Default)]
+pub struct SpherePdf {}
+
+impl SpherePdf {
+    pub fn sphere_pdf() -> SpherePdf {
+        SpherePdf {}
+    }
+}
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: &Vec3) -> f64 {
+        1.0 / (4.0 * std::f64::consts::PI)
+    }
+
+    fn generate(&self, rng: &mut Rng) -> Vec3 {
+        random_unit_vector(rng)
+    }
+}
+
+// proportional to the cosine with the normal, over the hemisphere around it
+#[derive(Copy, Clone)]
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+impl CosinePdf {
+    pub fn cosine_pdf(w: &Vec3) -> CosinePdf {
+        CosinePdf {
+            uvw: Onb::onb(w),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let cosine_theta = dot(&unit_vector(*direction), &self.uvw.w());
+        f64::max(0.0, cosine_theta / std::f64::consts::PI)
+    }
+
+    fn generate(&self, rng: &mut Rng) -> Vec3 {
+        self.uvw.transform(&random_cosine_direction(rng))
+    }
+}
+
+// directions a rough surface scatters wo into, through the microfacet normals visible
+// from wo. surfaces with an eta also transmit, refracting with the odds the fresnel
+// reflectance of the sampled normal leaves
+#[derive(Copy, Clone)]
+pub struct GgxPdf {
+    uvw: Onb,
+    wo: Vec3,
+    ggx: Ggx,
+    eta: Option<f64>,
+}
+
+impl GgxPdf {
+    // wo points away from the surface, on the side of the normal
+    pub fn ggx_pdf(normal: &Vec3, wo: &Vec3, ggx: &Ggx, eta: Option<f64>) -> GgxPdf {
+        let uvw = Onb::onb(normal);
+        GgxPdf {
+            uvw,
+            wo: uvw.to_local(&unit_vector(*wo)),
+            ggx: *ggx,
+            eta,
+        }
+    }
+}
+
+impl Pdf for GgxPdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let wo = self.wo;
+        let wi = self.uvw.to_local(&unit_vector(*direction));
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+
+        if wi.z() > 0.0 {
+            let h = unit_vector(wo + wi);
+            let pdf = self.ggx.visible_pdf(&wo, &h) / (4.0 * dot(&wo, &h));
+            return match self.eta {
+                Some(eta) => pdf * fresnel_dielectric(dot(&wo, &h), eta),
+                None => pdf,
+            };
+        }
+
+        let eta = match self.eta {
+            Some(eta) => eta,
+            None => return 0.0,
+        };
+        let h = refraction_half_vector(&wo, &wi, eta);
+        let (cos_o, cos_i) = (dot(&wo, &h), dot(&wi, &h));
+        if cos_o <= 0.0 || cos_i >= 0.0 {
+            return 0.0;
+        }
+        let denom = cos_o + eta * cos_i;
+        let dh_dwi = eta * eta * cos_i.abs() / (denom * denom);
+        return self.ggx.visible_pdf(&wo, &h) * (1.0 - fresnel_dielectric(cos_o, eta)) * dh_dwi;
+    }
+
+    fn generate(&self, rng: &mut Rng) -> Vec3 {
+        let wo = self.wo;
+        let h = self.ggx.sample_visible_normal(&wo, random_double(rng), random_double(rng));
+
+        if let Some(eta) = self.eta {
+            if random_double(rng) >= fresnel_dielectric(dot(&wo, &h), eta) {
+                if let Some(wi) = refract_local(&wo, &h, eta) {
+                    return self.uvw.transform(&wi);
+                }
+            }
+        }
+        return self.uvw.transform(&(2.0 * dot(&wo, &h) * h - wo));
+    }
+}
+
+// the lobes of Principled, one is picked with odds in proportion to its weight. they
+// share one frame, and are only put together when they are needed
+#[derive(Copy, Clone)]
+pub struct PrincipledPdf {
+    uvw: Onb,
+    wo: Vec3,
+    specular: Ggx,
+    clearcoat: Ggx,
+    eta: f64,
+    // diffuse, specular, clearcoat and transmission
+    weights: [f64; 4],
+}
+
+impl PrincipledPdf {
+    pub fn principled_pdf(normal: &Vec3, wo: &Vec3, specular: &Ggx, clearcoat: &Ggx, eta: f64, weights: [f64; 4]) -> PrincipledPdf {
+        let total: f64 = weights.iter().sum();
+        let weights = if total > 0.0 {
+            weights.map(|weight| weight / total)
+        } else {
+            [1.0, 0.0, 0.0, 0.0]
+        };
+        let uvw = Onb::onb(normal);
+
+        PrincipledPdf {
+            uvw,
+            wo: uvw.to_local(&unit_vector(*wo)),
+            specular: *specular,
+            clearcoat: *clearcoat,
+            eta,
+            weights,
+        }
+    }
+
+    fn lobe(&self, index: usize) -> Pdfs {
+        let ggx = |ggx: Ggx, eta: Option<f64>| GgxPdf {
+            uvw: self.uvw,
+            wo: self.wo,
+            ggx,
+            eta,
+        };
+        match index {
+            0 => Pdfs::COSINE(CosinePdf { uvw: self.uvw }),
+            1 => Pdfs::GGX(ggx(self.specular, None)),
+            2 => Pdfs::GGX(ggx(self.clearcoat, None)),
+            _ => Pdfs::GGX(ggx(self.specular, Some(self.eta))),
+        }
+    }
+}
+
+impl Pdf for PrincipledPdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let mut value = 0.0;
+        for (index, weight) in self.weights.iter().enumerate() {
+            if *weight > 0.0 {
+                value += weight * self.lobe(index).value(direction);
+            }
+        }
+        return value;
+    }
+
+    fn generate(&self, rng: &mut Rng) -> Vec3 {
+        let mut choice = random_double(rng);
+        for (index, weight) in self.weights.iter().enumerate() {
+            if choice < *weight {
+                return self.lobe(index).generate(rng);
+            }
+            choice -= weight;
+        }
+        // rounding left the choice past the last lobe
+        let last = self.weights.iter().rposition(|&weight| weight > 0.0).unwrap_or(0);
+        return self.lobe(last).generate(rng);
+    }
+}
+
+// directions from origin towards the surface of some objects, usually the lights
+pub struct HittablePdf<'a> {
+    objects: &'a dyn Hittable,
+    origin: Vec3,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn hittable_pdf(objects: &'a dyn Hittable, origin: &Vec3) -> HittablePdf<'a> {
+        HittablePdf {
+            objects,
+            origin: *origin,
+        }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.objects.pdf_value(&self.origin, direction)
+    }
+
+    fn generate(&self, rng: &mut Rng) -> Vec3 {
+        self.objects.random(&self.origin, rng)
+    }
+}
+
+// the distributions materials scatter with, stored by value in a ScatterRecord
+#[derive(Copy, Clone)]
+#[allow(non_camel_case_types)]
+pub enum Pdfs {
+    SPHERE(SpherePdf),
+    COSINE(CosinePdf),
+    GGX(GgxPdf),
+    PRINCIPLED(PrincipledPdf),
+}
+
+impl Pdf for Pdfs {
+    fn value(&self, direction: &Vec3) -> f64 {
+        match self {
+            Pdfs::SPHERE(p) => p.value(direction),
+            Pdfs::COSINE(p) => p.value(direction),
+            Pdfs::GGX(p) => p.value(direction),
+            Pdfs::PRINCIPLED(p) => p.value(direction),
+        }
+    }
+
+    fn generate(&self, rng: &mut Rng) -> Vec3 {
+        match self {
+            Pdfs::SPHERE(p) => p.generate(rng),
+            Pdfs::COSINE(p) => p.generate(rng),
+            Pdfs::GGX(p) => p.generate(rng),
+            Pdfs::PRINCIPLED(p) => p.generate(rng),
+        }
+    }
+}
+
+impl Default for Pdfs {
+    fn default() -> Self {
+        Pdfs::SPHERE(SpherePdf::sphere_pdf())
+    }
+}
+
+// weight of a sample from the first of two strategies, Veach's power heuristic with beta 2
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let a = pdf * pdf;
+    let b = other_pdf * other_pdf;
+    if a + b == 0.0 {
+        return 0.0;
+    }
+    return a / (a + b);
+}
@@ -0,0 +1,93 @@
+use crate::vec3::*;
+use crate::utility::Rng;
+
+const POINT_COUNT: usize = 256;
+
+pub struct Perlin {
+    randvec: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn perlin(rng: &mut Rng) -> Perlin {
+        let randvec = (0..POINT_COUNT).map(|_| unit_vector(Vec3::random_range(-1.0, 1.0, rng))).collect();
+
+        Perlin {
+            randvec,
+            perm_x: Perlin::generate_perm(rng),
+            perm_y: Perlin::generate_perm(rng),
+            perm_z: Perlin::generate_perm(rng),
+        }
+    }
+
+    pub fn noise(&self, p: &Vec3) -> f64 {
+        let u = p.x() - p.x().floor();
+        let v = p.y() - p.y().floor();
+        let w = p.z() - p.z().floor();
+
+        let i = p.x().floor() as i64;
+        let j = p.y().floor() as i64;
+        let k = p.z().floor() as i64;
+
+        let mut c = [[[Vec3::identity(); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, column) in plane.iter_mut().enumerate() {
+                for (dk, corner) in column.iter_mut().enumerate() {
+                    *corner = self.randvec[
+                        self.perm_x[((i + di as i64) & 255) as usize] ^
+                        self.perm_y[((j + dj as i64) & 255) as usize] ^
+                        self.perm_z[((k + dk as i64) & 255) as usize]
+                    ];
+                }
+            }
+        }
+
+        return Perlin::perlin_interp(&c, u, v, w);
+    }
+
+    // sum of octaves of noise with halving weights
+    pub fn turb(&self, p: &Vec3, depth: i32) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p *= 2.0;
+        }
+
+        return accum.abs();
+    }
+
+    fn generate_perm(rng: &mut Rng) -> Vec<usize> {
+        let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+        rng.shuffle(&mut p);
+        return p;
+    }
+
+    fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        // hermitian smoothing to hide the grid
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+        let mut accum = 0.0;
+
+        for (i, plane) in c.iter().enumerate() {
+            for (j, column) in plane.iter().enumerate() {
+                for (k, corner) in column.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight_v = Vec3::vec3(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                           * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                           * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                           * dot(corner, &weight_v);
+                }
+            }
+        }
+
+        return accum;
+    }
+}
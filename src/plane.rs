@@ -0,0 +1,61 @@
+use std::sync::Arc;
+use crate::ray::Ray;
+use crate::vec3::*;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::*;
+use crate::aabb::AABB;
+use crate::onb::Onb;
+
+// infinite plane through point, the texture coordinates repeat every world unit
+pub struct Plane {
+    point: Vec3,
+    mat: Arc<dyn Material>,
+    frame: Onb,
+}
+
+impl Plane {
+    pub fn plane(_point: Vec3, _normal: Vec3, _material: Arc<dyn Material>) -> Plane {
+        Plane {
+            point: _point,
+            mat: _material,
+            frame: Onb::onb(&_normal),
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let normal = self.frame.w();
+        let denom = dot(&normal, &r.direction());
+
+        // no hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = dot(&normal, &(self.point - r.origin())) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        let intersection = r.at(&t);
+        let local = self.frame.to_local(&(intersection - self.point));
+        let u = local.x() - local.x().floor();
+        let v = local.y() - local.y().floor();
+
+        rec.set_t(&t);
+        rec.set_p(&intersection);
+        rec.set_mat(&self.mat);
+        rec.set_face_normal(r, &normal);
+        rec.set_uv(&u, &v);
+        rec.set_tangents(&self.frame.u(), &self.frame.v());
+
+        return true;
+    }
+
+    // unbounded, the BVH builders keep it out of their cost estimates
+    fn bounding_box(&self) -> AABB {
+        AABB::universe()
+    }
+}
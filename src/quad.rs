@@ -0,0 +1,126 @@
+use std::sync::Arc;
+use crate::ray::Ray;
+use crate::vec3::*;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::material::*;
+use crate::aabb::AABB;
+use crate::utility::*;
+
+// parallelogram spanned by the edges u and v from the corner q
+pub struct Quad {
+    q: Vec3,
+    u: Vec3,
+    v: Vec3,
+    mat: Arc<dyn Material>,
+    bbox: AABB,
+    normal: Vec3,
+    d: f64,
+    w: Vec3,
+    area: f64,
+}
+
+impl Quad {
+    pub fn quad(_q: Vec3, _u: Vec3, _v: Vec3, _material: Arc<dyn Material>) -> Quad {
+        let n = cross(&_u, &_v);
+        let normal = unit_vector(n);
+
+        // compute the bounding box of all four vertices
+        let bbox_diagonal1 = AABB::aabb(&_q, &(_q + _u + _v));
+        let bbox_diagonal2 = AABB::aabb(&(_q + _u), &(_q + _v));
+
+        Quad {
+            q: _q,
+            u: _u,
+            v: _v,
+            mat: _material,
+            bbox: AABB::aabb_boxes(&bbox_diagonal1, &bbox_diagonal2).pad_to_minimums(),
+            normal,
+            d: dot(&normal, &_q),
+            w: n / dot(&n, &n),
+            area: n.length(),
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let denom = dot(&self.normal, &r.direction());
+
+        // no hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        // return false if the hit point parameter t is outside the ray interval
+        let t = (self.d - dot(&self.normal, &r.origin())) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        // determine if the hit point lies within the planar shape using its plane coordinates
+        let intersection = r.at(&t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = dot(&self.w, &cross(&planar_hitpt_vector, &self.v));
+        let beta = dot(&self.w, &cross(&self.u, &planar_hitpt_vector));
+
+        let unit_interval = Interval::interval(0.0, 1.0);
+        if !unit_interval.contains(alpha) || !unit_interval.contains(beta) {
+            return false;
+        }
+
+        rec.set_t(&t);
+        rec.set_p(&intersection);
+        rec.set_mat(&self.mat);
+        rec.set_face_normal(r, &self.normal);
+        rec.set_uv(&alpha, &beta);
+        rec.set_tangents(&self.u, &self.v);
+
+        return true;
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        if !self.hit(&Ray::ray(origin, direction), Interval::interval(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        // convert the uniform density over the area to one over solid angle
+        let distance_squared = rec.t() * rec.t() * direction.length_squared();
+        let cosine = f64::abs(dot(direction, &rec.normal()) / direction.length());
+
+        return distance_squared / (cosine * self.area);
+    }
+
+    fn random(&self, origin: &Vec3, rng: &mut Rng) -> Vec3 {
+        let p = self.q + (random_double(rng) * self.u) + (random_double(rng) * self.v);
+        return p - *origin;
+    }
+}
+
+// returns the 3D box (six sides) that contains the two opposite vertices a & b
+pub fn make_box(a: &Vec3, b: &Vec3, mat: &Arc<dyn Material>) -> HittableList {
+    let mut sides = HittableList::hittable_list();
+
+    // construct the two opposite vertices with the minimum and maximum coordinates
+    let min = Vec3::vec3(f64::min(a.x(), b.x()), f64::min(a.y(), b.y()), f64::min(a.z(), b.z()));
+    let max = Vec3::vec3(f64::max(a.x(), b.x()), f64::max(a.y(), b.y()), f64::max(a.z(), b.z()));
+
+    let dx = Vec3::vec3(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::vec3(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::vec3(0.0, 0.0, max.z() - min.z());
+
+    sides.add(Box::new(Quad::quad(Vec3::vec3(min.x(), min.y(), max.z()), dx, dy, mat.clone())));  // front
+    sides.add(Box::new(Quad::quad(Vec3::vec3(max.x(), min.y(), max.z()), -dz, dy, mat.clone()))); // right
+    sides.add(Box::new(Quad::quad(Vec3::vec3(max.x(), min.y(), min.z()), -dx, dy, mat.clone()))); // back
+    sides.add(Box::new(Quad::quad(Vec3::vec3(min.x(), min.y(), min.z()), dz, dy, mat.clone())));  // left
+    sides.add(Box::new(Quad::quad(Vec3::vec3(min.x(), max.y(), max.z()), dx, -dz, mat.clone()))); // top
+    sides.add(Box::new(Quad::quad(Vec3::vec3(min.x(), min.y(), min.z()), dx, dz, mat.clone())));  // bottom
+
+    return sides;
+}
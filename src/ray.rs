@@ -1,9 +1,14 @@
 use crate::vec3::Vec3;
 
+#[derive(Copy, Clone)]
 pub struct Ray {
     orig: Vec3,
     dir: Vec3,
-    tm: f64
+    tm: f64,
+    // in nanometers, only set in spectral mode
+    wavelength: Option<f64>,
+    // drawn from the random stream of the path, for objects that need randomness to be hit
+    seed: u64,
 }
 
 impl Ray {
@@ -11,7 +16,9 @@ impl Ray {
         Ray {
             orig: *origin,
             dir: *direction,
-            tm: 0.0
+            tm: 0.0,
+            wavelength: None,
+            seed: 0,
         }
     }
 
@@ -20,6 +27,8 @@ impl Ray {
             orig: *origin,
             dir: *direction,
             tm: *time,
+            wavelength: None,
+            seed: 0,
         }
     }
 
@@ -40,6 +49,14 @@ impl Ray {
         self.tm
     }
 
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
     pub fn at(&self, t: &f64) -> Vec3{
         self.orig + *t * self.dir
     }
@@ -55,4 +72,12 @@ impl Ray {
     pub fn set_time(&mut self, time: &f64) {
         self.tm = *time;
     }
+
+    pub fn set_wavelength(&mut self, wavelength: &Option<f64>) {
+        self.wavelength = *wavelength;
+    }
+
+    pub fn set_seed(&mut self, seed: &u64) {
+        self.seed = *seed;
+    }
 }
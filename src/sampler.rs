@@ -0,0 +1,425 @@
+use std::sync::OnceLock;
+use crate::utility::*;
+
+// the sequences the camera draws its pixel, lens and time samples from
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SamplerKind {
+    // independent uniform samples
+    Random,
+    // jittered strata, correlated multi-jittered for pairs of dimensions
+    Stratified,
+    // radical inverses in the first prime bases, randomly shifted per pixel
+    Halton,
+    // the sobol (0,2)-sequence, owen scrambled per pixel
+    Sobol,
+    // one sobol sequence for the whole image, shifted per pixel by a blue noise mask so
+    // the remaining error is spread out as high frequency noise
+    BlueNoise,
+}
+
+impl SamplerKind {
+    pub fn from_name(name: &str) -> Option<SamplerKind> {
+        match name.to_ascii_lowercase().as_str() {
+            "random" => Some(SamplerKind::Random),
+            "stratified" => Some(SamplerKind::Stratified),
+            "halton" => Some(SamplerKind::Halton),
+            "sobol" => Some(SamplerKind::Sobol),
+            "blue_noise" | "bluenoise" => Some(SamplerKind::BlueNoise),
+            _ => None,
+        }
+    }
+}
+
+// draws the samples of a single pixel, every value depends only on the pixel, the sample
+// index and the dimension, so pixels can be rendered in any order. dimensions are handed
+// out in the order they are asked for and start again with every sample
+pub struct PixelSampler {
+    kind: SamplerKind,
+    x: u32,
+    y: u32,
+    samples: u32,
+    seed: u64,
+    sample: u32,
+    dimension: u32,
+}
+
+impl PixelSampler {
+    pub fn pixel_sampler(kind: SamplerKind, x: u32, y: u32, samples: u32, seed: u64) -> PixelSampler {
+        PixelSampler {
+            kind,
+            x,
+            y,
+            samples: samples.max(1),
+            seed,
+            sample: 0,
+            dimension: 0,
+        }
+    }
+
+    pub fn start_sample(&mut self, index: u32) {
+        self.sample = index;
+        self.dimension = 0;
+    }
+
+    pub fn get_1d(&mut self, rng: &mut Rng) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        match self.kind {
+            SamplerKind::Random => random_double(rng),
+            SamplerKind::Stratified => {
+                let p = self.hash(dimension);
+                let stratum = permute(self.sample, self.samples, p);
+                (stratum as f64 + random_double(rng)) / self.samples as f64
+            }
+            _ => self.sequence(dimension, rng),
+        }
+    }
+
+    pub fn get_2d(&mut self, rng: &mut Rng) -> (f64, f64) {
+        if self.kind != SamplerKind::Stratified {
+            return (self.get_1d(rng), self.get_1d(rng));
+        }
+
+        let dimension = self.dimension;
+        self.dimension += 2;
+        return cmj(self.sample, self.samples, self.hash(dimension));
+    }
+
+    fn sequence(&self, dimension: u32, rng: &mut Rng) -> f64 {
+        if dimension as usize >= SOBOL_MATRICES.len() {
+            return random_double(rng);
+        }
+
+        match self.kind {
+            SamplerKind::Halton => {
+                let shift = to_unit(self.hash(dimension));
+                fract(radical_inverse(PRIMES[dimension as usize], self.sample as u64) + shift)
+            }
+            SamplerKind::Sobol => {
+                let bits = sobol(self.sample, dimension);
+                to_unit(owen_scramble(bits, self.hash(dimension)))
+            }
+            SamplerKind::BlueNoise => {
+                // the same scramble for every pixel, only the mask shift differs between them
+                let bits = sobol(self.sample, dimension);
+                let value = to_unit(owen_scramble(bits, mix_seed(self.seed, dimension as u64) as u32));
+                fract(value + blue_noise_shift(self.x, self.y, dimension, self.seed))
+            }
+            _ => random_double(rng),
+        }
+    }
+
+    fn hash(&self, dimension: u32) -> u32 {
+        let pixel = ((self.y as u64) << 32) | self.x as u64;
+        return mix_seed(mix_seed(self.seed, pixel), dimension as u64) as u32;
+    }
+}
+
+fn to_unit(bits: u32) -> f64 {
+    bits as f64 / 4294967296.0
+}
+
+fn fract(x: f64) -> f64 {
+    x - x.floor()
+}
+
+const PRIMES: [u32; 8] = [2, 3, 5, 7, 11, 13, 17, 19];
+
+fn radical_inverse(base: u32, mut index: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_n = 1.0;
+    let mut reversed = 0u64;
+    while index > 0 {
+        let next = index / base as u64;
+        reversed = reversed * base as u64 + (index - next * base as u64);
+        inv_base_n *= inv_base;
+        index = next;
+    }
+    return f64::min(reversed as f64 * inv_base_n, 1.0 - f64::EPSILON);
+}
+
+// generator matrices for the first eight dimensions, from the Joe-Kuo direction numbers
+// as (degree, coefficients, initial direction numbers)
+const SOBOL_MATRICES: [[u32; 32]; 8] = [
+    van_der_corput(),
+    sobol_matrix(1, 0, &[1]),
+    sobol_matrix(2, 1, &[1, 3]),
+    sobol_matrix(3, 1, &[1, 3, 1]),
+    sobol_matrix(3, 2, &[1, 1, 1]),
+    sobol_matrix(4, 1, &[1, 1, 3, 3]),
+    sobol_matrix(4, 4, &[1, 3, 5, 13]),
+    sobol_matrix(5, 2, &[1, 1, 5, 5, 17]),
+];
+
+const fn van_der_corput() -> [u32; 32] {
+    let mut v = [0u32; 32];
+    let mut i = 0;
+    while i < 32 {
+        v[i] = 1 << (31 - i);
+        i += 1;
+    }
+    return v;
+}
+
+const fn sobol_matrix(s: usize, a: u32, m: &[u32]) -> [u32; 32] {
+    let mut v = [0u32; 32];
+    let mut i = 0;
+    while i < 32 {
+        if i < s {
+            v[i] = m[i] << (31 - i);
+        } else {
+            v[i] = v[i - s] ^ (v[i - s] >> s);
+            let mut k = 1;
+            while k < s {
+                if (a >> (s - 1 - k)) & 1 == 1 {
+                    v[i] ^= v[i - k];
+                }
+                k += 1;
+            }
+        }
+        i += 1;
+    }
+    return v;
+}
+
+fn sobol(mut index: u32, dimension: u32) -> u32 {
+    let matrix = &SOBOL_MATRICES[dimension as usize];
+    let mut result = 0;
+    let mut bit = 0;
+    while index != 0 {
+        if index & 1 == 1 {
+            result ^= matrix[bit];
+        }
+        index >>= 1;
+        bit += 1;
+    }
+    return result;
+}
+
+// nested uniform scrambling with the Laine-Karras hash, as in Burley's "Practical
+// Hash-based Owen Scrambling", keeps the stratification of the sequence
+fn owen_scramble(bits: u32, seed: u32) -> u32 {
+    let mut x = bits.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    return x.reverse_bits();
+}
+
+// a random permutation of 0..len picked by p, Kensler's "Correlated Multi-Jittered Sampling"
+fn permute(mut i: u32, len: u32, p: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < len {
+            break;
+        }
+    }
+    return (i.wrapping_add(p)) % len;
+}
+
+fn hash_unit(mut i: u32, p: u32) -> f64 {
+    i ^= p;
+    i ^= i >> 17;
+    i ^= i >> 10;
+    i = i.wrapping_mul(0xb36534e5);
+    i ^= i >> 12;
+    i ^= i >> 21;
+    i = i.wrapping_mul(0x93fc4795);
+    i ^= 0xdf6e307f;
+    i ^= i >> 17;
+    i = i.wrapping_mul(1 | p >> 18);
+    return to_unit(i);
+}
+
+// sample s of n on an m by ceil(n / m) grid, stratified in 2d and in both 1d projections
+fn cmj(s: u32, n: u32, p: u32) -> (f64, f64) {
+    let m = f64::sqrt(n as f64) as u32;
+    let rows = n.div_ceil(m);
+    let s = permute(s, n, p.wrapping_mul(0x51633e2d));
+    let sx = permute(s % m, m, p.wrapping_mul(0x68bc21eb));
+    let sy = permute(s / m, rows, p.wrapping_mul(0x02e5be93));
+    let jx = hash_unit(s, p.wrapping_mul(0x967a889b));
+    let jy = hash_unit(s, p.wrapping_mul(0x368cc8b7));
+    let x = (sx as f64 + (sy as f64 + jx) / rows as f64) / m as f64;
+    let y = (s as f64 + jy) / n as f64;
+    return (x, y);
+}
+
+const MASK_SIZE: usize = 64;
+
+// the blue noise value of a pixel, each dimension reads the mask at its own offset
+fn blue_noise_shift(x: u32, y: u32, dimension: u32, seed: u64) -> f64 {
+    let offset = mix_seed(seed, 0x5eed ^ dimension as u64);
+    let mx = (x as usize + (offset as usize & 0xffff)) % MASK_SIZE;
+    let my = (y as usize + ((offset >> 16) as usize & 0xffff)) % MASK_SIZE;
+    return blue_noise_mask()[my * MASK_SIZE + mx];
+}
+
+fn blue_noise_mask() -> &'static [f64] {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    MASK.get_or_init(void_and_cluster)
+}
+
+// Ulichney's void and cluster method on a tiling mask, ranks are spread evenly over [0,1)
+fn void_and_cluster() -> Vec<f64> {
+    const SIGMA: f64 = 1.5;
+    const RADIUS: i32 = 6;
+    let n = MASK_SIZE * MASK_SIZE;
+
+    let mut kernel = Vec::new();
+    for dy in -RADIUS..=RADIUS {
+        for dx in -RADIUS..=RADIUS {
+            let weight = f64::exp(-((dx * dx + dy * dy) as f64) / (2.0 * SIGMA * SIGMA));
+            kernel.push((dx, dy, weight));
+        }
+    }
+
+    // the energy of a pixel is the gaussian weighted count of the set pixels around it
+    let splat = |energy: &mut [f64], index: usize, sign: f64| {
+        let (x, y) = ((index % MASK_SIZE) as i32, (index / MASK_SIZE) as i32);
+        for &(dx, dy, weight) in &kernel {
+            let nx = (x + dx).rem_euclid(MASK_SIZE as i32) as usize;
+            let ny = (y + dy).rem_euclid(MASK_SIZE as i32) as usize;
+            energy[ny * MASK_SIZE + nx] += sign * weight;
+        }
+    };
+    // the tightest cluster is the set pixel with the most energy, the largest void the
+    // empty one with the least
+    let tightest_cluster = |pattern: &[bool], energy: &[f64]| {
+        (0..n).filter(|&i| pattern[i]).max_by(|&a, &b| energy[a].total_cmp(&energy[b])).unwrap()
+    };
+    let largest_void = |pattern: &[bool], energy: &[f64]| {
+        (0..n).filter(|&i| !pattern[i]).min_by(|&a, &b| energy[a].total_cmp(&energy[b])).unwrap()
+    };
+
+    // a random initial pattern, relaxed by moving points from clusters into voids
+    let mut rng = Rng::with_seed(0);
+    let initial_count = n / 10;
+    let mut pattern = vec![false; n];
+    let mut energy = vec![0.0; n];
+    let mut placed = 0;
+    while placed < initial_count {
+        let index = rng.usize(..n);
+        if !pattern[index] {
+            pattern[index] = true;
+            splat(&mut energy, index, 1.0);
+            placed += 1;
+        }
+    }
+    loop {
+        let cluster = tightest_cluster(&pattern, &energy);
+        pattern[cluster] = false;
+        splat(&mut energy, cluster, -1.0);
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0usize; n];
+
+    // the initial points are ranked by removing them cluster first
+    let mut removing = pattern.clone();
+    let mut removing_energy = energy.clone();
+    for rank in (0..initial_count).rev() {
+        let cluster = tightest_cluster(&removing, &removing_energy);
+        removing[cluster] = false;
+        splat(&mut removing_energy, cluster, -1.0);
+        ranks[cluster] = rank;
+    }
+
+    // and the rest by filling voids, which past half full is the same as taking the
+    // tightest cluster of the empty pixels
+    for rank in initial_count..n {
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        ranks[void] = rank;
+    }
+
+    return ranks.into_iter().map(|rank| (rank as f64 + 0.5) / n as f64).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permute_is_a_bijection() {
+        for len in [1, 2, 3, 7, 16, 100, 257] {
+            for p in [0, 1, 0x12345678, 0xdeadbeef] {
+                let mut seen = vec![false; len as usize];
+                for i in 0..len {
+                    let j = permute(i, len, p) as usize;
+                    assert!(!seen[j], "len {} p {:#x} maps two indices to {}", len, p, j);
+                    seen[j] = true;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cmj_has_one_sample_per_row_and_column() {
+        let n = 16;
+        for p in [0, 1, 0x12345678, 0xdeadbeef] {
+            let mut columns = vec![0; n as usize];
+            let mut rows = vec![0; n as usize];
+            for s in 0..n {
+                let (x, y) = cmj(s, n, p);
+                assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+                columns[(x * n as f64) as usize] += 1;
+                rows[(y * n as f64) as usize] += 1;
+            }
+            assert!(columns.iter().all(|&count| count == 1), "{:?}", columns);
+            assert!(rows.iter().all(|&count| count == 1), "{:?}", rows);
+        }
+    }
+
+    #[test]
+    fn sobol_fills_every_interval_once() {
+        for dimension in 0..SOBOL_MATRICES.len() as u32 {
+            for k in 0..=10 {
+                for seed in [None, Some(0x9e3779b9)] {
+                    let mut seen = vec![false; 1 << k];
+                    for index in 0..1u32 << k {
+                        let mut bits = sobol(index, dimension);
+                        if let Some(seed) = seed {
+                            bits = owen_scramble(bits, seed);
+                        }
+                        let interval = (bits as u64 >> (32 - k)) as usize;
+                        assert!(!seen[interval], "dimension {} k {} interval {}", dimension, k, interval);
+                        seen[interval] = true;
+                    }
+                }
+            }
+        }
+    }
+}
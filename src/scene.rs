@@ -0,0 +1,754 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::camera::{Background, Camera};
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::material::*;
+use crate::sphere::Sphere;
+use crate::quad::{make_box, Quad};
+use crate::triangle::Triangle;
+use crate::disk::Disk;
+use crate::plane::Plane;
+use crate::obj::load_obj;
+use crate::transform::{Mat4, Transform};
+use crate::constant_medium::ConstantMedium;
+use crate::texture::*;
+use crate::sampler::SamplerKind;
+use crate::utility::Rng;
+use crate::vec3::Vec3;
+
+// a scene file is a list of directives, one per line, `#` starts a comment:
+//
+//   look_from 13 2 3                      camera settings, see apply_camera_setting
+//   texture floor checker 0.3 0 0 0 1 1 1 texture <name> <type> <parameters>
+//   material glass dielectric 1.5         material <name> <type> <parameters>, colors
+//                                         can be given as (r g b) or a texture name
+//   sphere 0 1 0 1 glass                  objects reference materials by name, see
+//   quad 0 0 0 1 0 0 0 1 0 white          parse_object for sphere, moving_sphere, quad,
+//                                         triangle, disk, plane and box
+//   mesh models/teapot.obj                Wavefront OBJ model with the materials of its
+//                                         MTL files, or with a single scene material
+//   object pot mesh models/teapot.obj     named object that is only placed by instances,
+//   instance pot rotate_y 30 translate 2 0 0   transformed in the order given
+//   medium fog_box 0.01 smoke             volume inside a named object, see parse_object
+//   background 0 0 0                      solid background color, or `background sky`
+//   sampler blue_noise                    random, stratified, halton, sobol or blue_noise
+//   noise_threshold 0.01                  adaptive sampling, samples_per_pixel is the most
+//                                         a pixel takes, see Camera::set_noise_threshold
+//   min_samples 16                        samples a pixel takes before it can stop
+//   spectral                              trace a wavelength per path, for dispersion
+//
+// spheres, quads, triangles, disks and boxes with a diffuse_light material are also
+// sampled directly as lights
+pub struct Scene {
+    camera: Camera,
+    world: HittableList,
+    lights: HittableList,
+}
+
+impl Scene {
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    pub fn world(&self) -> &HittableList {
+        &self.world
+    }
+
+    // the emissive spheres, quads, triangles, disks and boxes, which are also in the world
+    pub fn lights(&self) -> &HittableList {
+        &self.lights
+    }
+
+    pub fn into_parts(self) -> (Camera, HittableList, HittableList) {
+        (self.camera, self.world, self.lights)
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        path: Option<PathBuf>,
+        line: usize,
+        text: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            SceneError::Parse { path, line, text, message } => {
+                match path {
+                    Some(path) => write!(f, "{}:{}: {}", path.display(), line, message)?,
+                    None => write!(f, "line {}: {}", line, message)?,
+                }
+                write!(f, "\n{:>5} | {}", line, text)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+pub fn load_scene(path: &Path) -> Result<Scene, SceneError> {
+    let source = std::fs::read_to_string(path).map_err(|error| SceneError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+
+    // files referenced by the scene are relative to the scene file
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    parse_scene_in(&source, base_dir).map_err(|e| match e {
+        SceneError::Parse { line, text, message, .. } => SceneError::Parse {
+            path: Some(path.to_path_buf()),
+            line,
+            text,
+            message,
+        },
+        e => e,
+    })
+}
+
+pub fn parse_scene(source: &str) -> Result<Scene, SceneError> {
+    parse_scene_in(source, Path::new("."))
+}
+
+pub fn parse_scene_in(source: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+    let mut parser = SceneParser::new(base_dir);
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let content = match text.find('#') {
+            Some(comment) => &text[..comment],
+            None => text,
+        };
+        let tokens: Vec<&str> = content.split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+
+        parser.directive(&tokens, line).map_err(|message| SceneError::Parse {
+            path: None,
+            line,
+            text: text.trim_end().to_string(),
+            message,
+        })?;
+    }
+
+    Ok(Scene {
+        camera: parser.camera,
+        world: parser.world,
+        lights: parser.lights,
+    })
+}
+
+const PRINCIPLED_PARAMETERS: [&str; 8] = ["base_color", "metallic", "roughness", "specular", "clearcoat", "sheen", "transmission", "ior"];
+
+// a named object, and whether it can be sampled as a light
+type Object = (Arc<dyn Hittable>, bool);
+
+struct SceneParser {
+    base_dir: PathBuf,
+    camera: Camera,
+    world: HittableList,
+    lights: HittableList,
+    // named definitions, with the line they were defined on
+    textures: HashMap<String, (Textures, usize)>,
+    materials: HashMap<String, (Arc<dyn Material>, usize)>,
+    objects: HashMap<String, (Object, usize)>,
+    // procedural textures are seeded from the file, so a scene always looks the same
+    rng: Rng,
+}
+
+impl SceneParser {
+    fn new(base_dir: &Path) -> SceneParser {
+        let mut camera = Camera::camera();
+        // the camera zeroes these, which would render an empty image
+        camera.set_samples_per_pixel(&10);
+        camera.set_max_depth(&10);
+        camera.set_focus_dist(&10.0);
+
+        SceneParser {
+            base_dir: base_dir.to_path_buf(),
+            camera,
+            world: HittableList::hittable_list(),
+            lights: HittableList::hittable_list(),
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            objects: HashMap::new(),
+            rng: Rng::with_seed(0),
+        }
+    }
+
+    fn directive(&mut self, tokens: &[&str], line: usize) -> Result<(), String> {
+        let args = &tokens[1..];
+        match tokens[0] {
+            "texture" => {
+                let (name, tex) = self.parse_texture(args)?;
+                define(&mut self.textures, "texture", name, tex, line)
+            }
+            "material" => {
+                let (name, mat) = self.parse_material(args)?;
+                define(&mut self.materials, "material", name, mat, line)
+            }
+            "object" => {
+                let name = match args.first() {
+                    Some(name) => name.to_string(),
+                    None => return Err("object expects a name followed by a shape".to_string()),
+                };
+                let object = self.parse_shape(&args[1..])?;
+                let light = self.is_light(&args[1..]);
+                define(&mut self.objects, "object", name, (Arc::from(object), light), line)
+            }
+            "instance" => {
+                let (object, light) = self.parse_instance(args)?;
+                self.add(object, light);
+                Ok(())
+            }
+            keyword if SHAPES.contains(&keyword) => {
+                let object = self.parse_shape(tokens)?;
+                self.add(object, self.is_light(tokens));
+                Ok(())
+            }
+            keyword => apply_camera_setting(&mut self.camera, keyword, args),
+        }
+    }
+
+    fn add(&mut self, object: Box<dyn Hittable>, light: bool) {
+        if !light {
+            self.world.add(object);
+            return;
+        }
+        let object: Arc<dyn Hittable> = Arc::from(object);
+        self.lights.add(Box::new(Arc::clone(&object)));
+        self.world.add(Box::new(object));
+    }
+
+    // shapes that can be sampled towards with an emissive material
+    fn is_light(&self, tokens: &[&str]) -> bool {
+        let sampled = matches!(tokens.first(), Some(&("sphere" | "moving_sphere" | "quad" | "triangle" | "disk" | "box")));
+        let emissive = tokens.last().and_then(|name| self.materials.get(*name)).is_some_and(|(mat, _)| mat.is_emissive());
+        sampled && emissive
+    }
+
+    fn texture(&self, name: &str) -> Result<Textures, String> {
+        match self.textures.get(name) {
+            Some((tex, _)) => Ok(tex.clone()),
+            None => Err(format!("unknown texture '{}', textures must be defined before they are used", name)),
+        }
+    }
+
+    fn material(&self, name: &str) -> Result<Arc<dyn Material>, String> {
+        match self.materials.get(name) {
+            Some((mat, _)) => Ok(mat.clone()),
+            None => Err(format!("unknown material '{}', materials must be defined before they are used", name)),
+        }
+    }
+
+    // a color is either three numbers or the name of a texture
+    fn color_or_texture(&self, what: &str, args: &[&str]) -> Result<Textures, String> {
+        match args.len() {
+            1 => self.texture(args[0]),
+            3 => Ok(Textures::from(vector(what, args)?)),
+            n => Err(format!("{} expects a color (r g b) or a texture name, found {} value(s)", what, n)),
+        }
+    }
+
+    // a gray value or a texture name
+    fn value_or_texture(&self, what: &str, args: &[&str]) -> Result<Textures, String> {
+        expect_count(what, args, 1, "a number or a texture name")?;
+        match parse_number(args[0]) {
+            Ok(value) => Ok(Textures::from(Vec3::vec3(value, value, value))),
+            Err(_) => self.texture(args[0]),
+        }
+    }
+
+    fn parse_texture(&mut self, args: &[&str]) -> Result<(String, Textures), String> {
+        if args.len() < 2 {
+            return Err("expected 'texture <name> <type> <parameters>'".to_string());
+        }
+        let name = args[0].to_string();
+        let params = &args[2..];
+
+        let tex = match args[1] {
+            "solid" => Textures::from(vector("solid color", params)?),
+            "checker" => {
+                // checker <scale> <even> <odd>, where even and odd are texture names or colors
+                let (even, odd) = match params.len() {
+                    3 => (self.texture(params[1])?, self.texture(params[2])?),
+                    7 => (Textures::from(vector("checker color", &params[1..4])?), Textures::from(vector("checker color", &params[4..7])?)),
+                    n => return Err(format!("checker expects a scale and two colors or texture names, found {} value(s)", n)),
+                };
+                let scale = positive("checker scale", parse_number(params[0])?)?;
+                Textures::CHECKER(CheckerTexture::new(scale, even, odd))
+            }
+            "image" => {
+                expect_count("image", params, 1, "a file path")?;
+                let path = self.base_dir.join(params[0]);
+                let image = ImageTexture::load(&path).map_err(|e| format!("cannot load image '{}': {}", path.display(), e))?;
+                Textures::IMAGE(image)
+            }
+            kind @ ("noise" | "turbulence" | "marble") => {
+                let kind = match kind {
+                    "noise" => NoiseKind::Noise,
+                    "turbulence" => NoiseKind::Turbulence,
+                    _ => NoiseKind::Marble,
+                };
+                let scale = positive("noise scale", single("noise", params)?)?;
+                Textures::NOISE(NoiseTexture::new(scale, kind, &mut self.rng))
+            }
+            other => return Err(format!("unknown texture type '{}', expected solid, checker, image, noise, turbulence or marble", other)),
+        };
+
+        Ok((name, tex))
+    }
+
+    fn parse_material(&self, args: &[&str]) -> Result<(String, Arc<dyn Material>), String> {
+        if args.len() < 2 {
+            return Err("expected 'material <name> <type> <parameters>'".to_string());
+        }
+        let name = args[0].to_string();
+        let params = &args[2..];
+
+        let mat: Arc<dyn Material> = match args[1] {
+            "lambertian" => Arc::new(Lambertian::from_texture(self.color_or_texture("lambertian albedo", params)?)),
+            "metal" => {
+                let (fuzz, albedo) = match params.split_last() {
+                    Some((fuzz, albedo)) => (parse_number(fuzz)?, albedo),
+                    None => return Err("metal expects an albedo (r g b or texture) and a fuzz".to_string()),
+                };
+                Arc::new(Metal::from_texture(self.color_or_texture("metal albedo", albedo)?, fuzz))
+            }
+            // dielectric <ior>, dielectric <bk7|fused_silica|sf11|diamond>, dielectric cauchy
+            // <a> <b>, or dielectric sellmeier <b1 b2 b3> <c1 c2 c3> with wavelengths in
+            // micrometers. glass only disperses light in spectral mode. any of them can end in
+            // tint <r g b> [distance], the color white light keeps after going the distance,
+            // 1 by default, through the glass
+            "dielectric" => {
+                let (index, tint) = match params.iter().position(|&token| token == "tint") {
+                    Some(at) => (&params[..at], Some(&params[at + 1..])),
+                    None => (params, None),
+                };
+                let mut dielectric = match index {
+                    ["cauchy", a, b] => Dielectric::from_dispersion(Dispersion::Cauchy(positive("cauchy a", parse_number(a)?)?, parse_number(b)?)),
+                    ["sellmeier", coefficients @ ..] => {
+                        expect_count("sellmeier", coefficients, 6, "three b and three c coefficients")?;
+                        let (b, c) = (vector("sellmeier b", &coefficients[..3])?, vector("sellmeier c", &coefficients[3..])?);
+                        Dielectric::from_dispersion(Dispersion::Sellmeier([b.x(), b.y(), b.z()], [c.x(), c.y(), c.z()]))
+                    }
+                    [name] => match Dielectric::from_name(name) {
+                        Some(glass) => glass,
+                        None => {
+                            let ir = parse_number(name).map_err(|_| format!("unknown glass '{}', expected an index of refraction, bk7, fused_silica, sf11 or diamond", name))?;
+                            Dielectric::new(positive("index of refraction", ir)?)
+                        }
+                    },
+                    _ => return Err("dielectric expects an index of refraction, a glass name, cauchy <a> <b> or sellmeier <b1 b2 b3> <c1 c2 c3>".to_string()),
+                };
+                if let Some(tint) = tint {
+                    let (color, distance) = match tint.len() {
+                        3 => (vector("tint", tint)?, 1.0),
+                        4 => (vector("tint", &tint[..3])?, positive("tint distance", parse_number(tint[3])?)?),
+                        n => return Err(format!("tint expects a color (r g b) and optionally a distance, found {} value(s)", n)),
+                    };
+                    dielectric.set_tint(&color, &distance);
+                }
+                Arc::new(dielectric)
+            }
+            // conductor <gold|copper|aluminium|silver> <roughness>, conductor <eta (r g b)>
+            // <k (r g b)> <roughness>, or conductor <color or texture> <roughness> for
+            // Schlick's approximation from the color head on
+            "conductor" => {
+                let (roughness, reflectance) = match params.split_last() {
+                    Some((roughness, reflectance)) => (parse_number(roughness)?, reflectance),
+                    None => return Err("conductor expects a metal name, a complex index of refraction or a color, and a roughness".to_string()),
+                };
+                let named = match reflectance {
+                    [name] => Conductor::from_name(name, roughness),
+                    _ => None,
+                };
+                let conductor = match named {
+                    Some(conductor) => conductor,
+                    None if reflectance.len() == 6 => Conductor::new(vector("eta", &reflectance[..3])?, vector("k", &reflectance[3..])?, roughness),
+                    None => Conductor::from_texture(self.color_or_texture("conductor color", reflectance)?, roughness),
+                };
+                Arc::new(conductor)
+            }
+            "rough_dielectric" => {
+                expect_count("rough_dielectric", params, 2, "an index of refraction and a roughness")?;
+                let ir = positive("index of refraction", parse_number(params[0])?)?;
+                Arc::new(RoughDielectric::new(ir, parse_number(params[1])?))
+            }
+            "principled" => Arc::new(self.parse_principled(params)?),
+            "diffuse_light" => Arc::new(DiffuseLight::from_texture(self.color_or_texture("diffuse_light color", params)?)),
+            "isotropic" => Arc::new(Isotropic::from_texture(self.color_or_texture("isotropic albedo", params)?)),
+            other => return Err(format!("unknown material type '{}', expected lambertian, metal, dielectric, conductor, rough_dielectric, principled, diffuse_light or isotropic", other)),
+        };
+
+        Ok((name, mat))
+    }
+
+    // principled [base_color <color or texture>] [metallic <value>] ... with any of the
+    // PRINCIPLED_PARAMETERS in any order, scalar values are a number or a texture name
+    fn parse_principled(&self, params: &[&str]) -> Result<Principled, String> {
+        let mut principled = Principled::new(Vec3::vec3(0.8, 0.8, 0.8));
+        let mut rest = params;
+        while let Some((&key, tail)) = rest.split_first() {
+            if !PRINCIPLED_PARAMETERS.contains(&key) {
+                return Err(format!("unknown principled parameter '{}', expected {}", key, PRINCIPLED_PARAMETERS.join(", ")));
+            }
+            let count = tail.iter().position(|token| PRINCIPLED_PARAMETERS.contains(token)).unwrap_or(tail.len());
+            let (values, next) = tail.split_at(count);
+
+            match key {
+                "base_color" => principled.set_base_color(&self.color_or_texture("principled base_color", values)?),
+                "ior" => principled.set_ior(&positive("ior", single("ior", values)?)?),
+                _ => {
+                    let tex = self.value_or_texture(key, values)?;
+                    match key {
+                        "metallic" => principled.set_metallic(&tex),
+                        "roughness" => principled.set_roughness(&tex),
+                        "specular" => principled.set_specular(&tex),
+                        "clearcoat" => principled.set_clearcoat(&tex),
+                        "sheen" => principled.set_sheen(&tex),
+                        _ => principled.set_transmission(&tex),
+                    }
+                }
+            }
+            rest = next;
+        }
+        Ok(principled)
+    }
+
+    fn parse_object(&self, keyword: &str, args: &[&str]) -> Result<Box<dyn Hittable>, String> {
+        let (geometry, mat_name) = match args.split_last() {
+            Some((mat_name, geometry)) => (geometry, *mat_name),
+            None => return Err(format!("expected geometry and a material name after '{}'", keyword)),
+        };
+        let mat = self.material(mat_name)?;
+
+        match keyword {
+            "moving_sphere" => {
+                expect_count(keyword, geometry, 7, "two centers (x y z) and a radius")?;
+                let center1 = vector("center", &geometry[..3])?;
+                let center2 = vector("center", &geometry[3..6])?;
+                let radius = positive("radius", parse_number(geometry[6])?)?;
+                Ok(Box::new(Sphere::sphere_moving(center1, center2, radius, mat)))
+            }
+            // corner q and the two edges u and v
+            "quad" => {
+                expect_count(keyword, geometry, 9, "a corner and two edges (x y z)")?;
+                let q = vector("corner", &geometry[..3])?;
+                let u = nonzero("edge", vector("edge", &geometry[3..6])?)?;
+                let v = nonzero("edge", vector("edge", &geometry[6..9])?)?;
+                Ok(Box::new(Quad::quad(q, u, v, mat)))
+            }
+            "triangle" => {
+                expect_count(keyword, geometry, 9, "three vertices (x y z)")?;
+                let a = vector("vertex", &geometry[..3])?;
+                let b = vector("vertex", &geometry[3..6])?;
+                let c = vector("vertex", &geometry[6..9])?;
+                Ok(Box::new(Triangle::triangle(a, b, c, mat)))
+            }
+            "disk" => {
+                expect_count(keyword, geometry, 7, "a center, a normal (x y z) and a radius")?;
+                let center = vector("center", &geometry[..3])?;
+                let normal = nonzero("normal", vector("normal", &geometry[3..6])?)?;
+                let radius = positive("radius", parse_number(geometry[6])?)?;
+                Ok(Box::new(Disk::disk(center, normal, radius, mat)))
+            }
+            "plane" => {
+                expect_count(keyword, geometry, 6, "a point and a normal (x y z)")?;
+                let point = vector("point", &geometry[..3])?;
+                let normal = nonzero("normal", vector("normal", &geometry[3..6])?)?;
+                Ok(Box::new(Plane::plane(point, normal, mat)))
+            }
+            // axis aligned box between two opposite corners
+            "box" => {
+                expect_count(keyword, geometry, 6, "two opposite corners (x y z)")?;
+                let a = vector("corner", &geometry[..3])?;
+                let b = vector("corner", &geometry[3..6])?;
+                Ok(Box::new(make_box(&a, &b, &mat)))
+            }
+            // volume filling a named object, usually with an isotropic material
+            "medium" => {
+                expect_count(keyword, geometry, 2, "a boundary object name and a density")?;
+                let boundary = match self.objects.get(geometry[0]) {
+                    Some(((object, _), _)) => Arc::clone(object),
+                    None => return Err(format!("unknown object '{}', objects must be defined before they are used", geometry[0])),
+                };
+                let density = positive("density", parse_number(geometry[1])?)?;
+                Ok(Box::new(ConstantMedium::constant_medium(boundary, density, mat)))
+            }
+            _ => {
+                expect_count(keyword, geometry, 4, "a center (x y z) and a radius")?;
+                let center = vector("center", &geometry[..3])?;
+                let radius = positive("radius", parse_number(geometry[3])?)?;
+                Ok(Box::new(Sphere::sphere(center, radius, mat)))
+            }
+        }
+    }
+
+    fn parse_shape(&self, tokens: &[&str]) -> Result<Box<dyn Hittable>, String> {
+        match tokens.first() {
+            Some(&"mesh") => self.parse_mesh(&tokens[1..]),
+            Some(keyword) if SHAPES.contains(keyword) => self.parse_object(keyword, &tokens[1..]),
+            Some(other) => Err(format!("unknown shape '{}', expected one of {}", other, SHAPES.join(", "))),
+            None => Err("expected a shape".to_string()),
+        }
+    }
+
+    fn parse_instance(&self, args: &[&str]) -> Result<(Box<dyn Hittable>, bool), String> {
+        let (name, mut rest) = match args.split_first() {
+            Some((name, rest)) => (*name, rest),
+            None => return Err("instance expects an object name followed by transforms".to_string()),
+        };
+        let (object, light) = match self.objects.get(name) {
+            Some(((object, light), _)) => (Arc::clone(object), *light),
+            None => return Err(format!("unknown object '{}', objects must be defined before they are used", name)),
+        };
+        if rest.is_empty() {
+            return Ok((Box::new(object), light));
+        }
+
+        let mut matrix = Mat4::identity();
+        while let Some((op, tail)) = rest.split_first() {
+            // scale takes either one uniform factor or three
+            let count = match *op {
+                "translate" => 3,
+                "scale" if tail.len() >= 3 && tail[..3].iter().all(|t| parse_number(t).is_ok()) => 3,
+                _ => 1,
+            };
+            if tail.len() < count {
+                return Err(format!("{} expects {} value(s), found {}", op, count, tail.len()));
+            }
+            let values = &tail[..count];
+            let step = match *op {
+                "translate" => Mat4::translation(&vector("translate", values)?),
+                "rotate_x" => Mat4::rotation_x(parse_number(values[0])?),
+                "rotate_y" => Mat4::rotation_y(parse_number(values[0])?),
+                "rotate_z" => Mat4::rotation_z(parse_number(values[0])?),
+                "scale" if count == 3 => Mat4::scaling(&vector("scale", values)?),
+                "scale" => {
+                    let factor = parse_number(values[0])?;
+                    Mat4::scaling(&Vec3::vec3(factor, factor, factor))
+                }
+                other => return Err(format!("unknown transform '{}', expected translate, rotate_x, rotate_y, rotate_z or scale", other)),
+            };
+            matrix = step * matrix;
+            rest = &tail[count..];
+        }
+
+        match Transform::transform(object, &matrix) {
+            Some(transform) => Ok((Box::new(transform), light)),
+            None => Err("the transform cannot be inverted, check for a zero scale".to_string()),
+        }
+    }
+
+    fn parse_mesh(&self, args: &[&str]) -> Result<Box<dyn Hittable>, String> {
+        let (file, material) = match args {
+            [file] => (file, None),
+            [file, mat_name] => (file, Some(self.material(mat_name)?)),
+            _ => return Err(format!("mesh expects a file path and an optional material name, found {} value(s)", args.len())),
+        };
+        let path = self.base_dir.join(file);
+        let mesh = load_obj(&path, material.as_ref()).map_err(|e| format!("cannot load mesh {}", e))?;
+        Ok(Box::new(mesh))
+    }
+}
+
+const SHAPES: [&str; 9] = ["sphere", "moving_sphere", "quad", "triangle", "disk", "plane", "box", "mesh", "medium"];
+
+fn define<T>(map: &mut HashMap<String, (T, usize)>, kind: &str, name: String, value: T, line: usize) -> Result<(), String> {
+    if let Some((_, defined)) = map.get(&name) {
+        return Err(format!("{} '{}' is already defined on line {}", kind, name, defined));
+    }
+    map.insert(name, (value, line));
+    Ok(())
+}
+
+fn apply_camera_setting(camera: &mut Camera, keyword: &str, args: &[&str]) -> Result<(), String> {
+    match keyword {
+        "aspect_ratio" => camera.set_aspect_ratio(&positive(keyword, single(keyword, args)?)?),
+        "image_width" => camera.set_image_width(&integer(keyword, args)?),
+        "samples_per_pixel" => camera.set_samples_per_pixel(&integer(keyword, args)?),
+        "max_depth" => camera.set_max_depth(&integer(keyword, args)?),
+        "roulette_depth" => camera.set_roulette_depth(&integer(keyword, args)?),
+        "noise_threshold" => camera.set_noise_threshold(&positive(keyword, single(keyword, args)?)?),
+        "min_samples" => camera.set_min_samples(&integer(keyword, args)?),
+        "fov" => camera.set_fov(&positive(keyword, single(keyword, args)?)?),
+        "look_from" => camera.set_look_from(&vector(keyword, args)?),
+        "look_at" => camera.set_look_at(&vector(keyword, args)?),
+        "v_up" => camera.set_v_up(&vector(keyword, args)?),
+        "defocus_angle" => camera.set_defocus_angle(&single(keyword, args)?),
+        "focus_dist" => camera.set_focus_dist(&positive(keyword, single(keyword, args)?)?),
+        "sampler" => {
+            expect_count(keyword, args, 1, "a sampler name")?;
+            let sampler = SamplerKind::from_name(args[0]).ok_or(format!(
+                "unknown sampler '{}', expected random, stratified, halton, sobol or blue_noise",
+                args[0]
+            ))?;
+            camera.set_sampler(&sampler);
+        }
+        "spectral" => {
+            expect_count(keyword, args, 0, "no values")?;
+            camera.set_spectral(&true);
+        }
+        "background" => {
+            if args == ["sky"] {
+                camera.set_background(&Background::Sky);
+            } else {
+                camera.set_background(&Background::Color(vector("background color", args)?));
+            }
+        }
+        _ => return Err(format!("unknown directive '{}'", keyword)),
+    }
+    Ok(())
+}
+
+fn expect_count(what: &str, args: &[&str], count: usize, expected: &str) -> Result<(), String> {
+    if args.len() != count {
+        return Err(format!("{} expects {}, found {} value(s)", what, expected, args.len()));
+    }
+    Ok(())
+}
+
+// accepts plain numbers as well as fractions such as 16/9
+pub fn parse_number(token: &str) -> Result<f64, String> {
+    let invalid = || format!("'{}' is not a number", token);
+    let value = match token.split_once('/') {
+        Some((num, den)) => {
+            let num: f64 = num.parse().map_err(|_| invalid())?;
+            let den: f64 = den.parse().map_err(|_| invalid())?;
+            num / den
+        }
+        None => token.parse().map_err(|_| invalid())?,
+    };
+    if !value.is_finite() {
+        return Err(invalid());
+    }
+    Ok(value)
+}
+
+fn single(what: &str, args: &[&str]) -> Result<f64, String> {
+    expect_count(what, args, 1, "a single number")?;
+    parse_number(args[0])
+}
+
+fn positive(what: &str, value: f64) -> Result<f64, String> {
+    if value <= 0.0 {
+        return Err(format!("{} must be positive, found {}", what, value));
+    }
+    Ok(value)
+}
+
+fn integer(what: &str, args: &[&str]) -> Result<i32, String> {
+    expect_count(what, args, 1, "a single whole number")?;
+    match args[0].parse::<i32>() {
+        Ok(value) if value > 0 => Ok(value),
+        _ => Err(format!("{} must be a positive whole number, found '{}'", what, args[0])),
+    }
+}
+
+fn vector(what: &str, args: &[&str]) -> Result<Vec3, String> {
+    expect_count(what, args, 3, "three numbers (x y z)")?;
+    Ok(Vec3::vec3(parse_number(args[0])?, parse_number(args[1])?, parse_number(args[2])?))
+}
+
+fn nonzero(what: &str, value: Vec3) -> Result<Vec3, String> {
+    if value.length_squared() == 0.0 {
+        return Err(format!("{} must not be the zero vector", what));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the line and message of a scene that fails to parse
+    fn parse_error(source: &str) -> (usize, String) {
+        match parse_scene(source) {
+            Ok(_) => panic!("the scene parsed"),
+            Err(SceneError::Parse { line, message, .. }) => (line, message),
+            Err(e) => panic!("not a parse error: {}", e),
+        }
+    }
+
+    #[test]
+    fn unknown_directive() {
+        let (line, message) = parse_error("image_width 100\n\n# comment\nimage_wdith 200\n");
+        assert_eq!(line, 4);
+        assert_eq!(message, "unknown directive 'image_wdith'");
+    }
+
+    #[test]
+    fn wrong_argument_count() {
+        let (line, message) = parse_error("material white lambertian 1 1 1\nsphere 0 0 0 white\n");
+        assert_eq!(line, 2);
+        assert_eq!(message, "sphere expects a center (x y z) and a radius, found 3 value(s)");
+
+        let (line, message) = parse_error("look_at 0 0\n");
+        assert_eq!(line, 1);
+        assert_eq!(message, "look_at expects three numbers (x y z), found 2 value(s)");
+    }
+
+    #[test]
+    fn values_that_must_be_positive() {
+        let (line, message) = parse_error("material white lambertian 1 1 1\nsphere 0 0 0 -1 white\n");
+        assert_eq!(line, 2);
+        assert_eq!(message, "radius must be positive, found -1");
+
+        let (line, message) = parse_error("fov 60\nfov 0\n");
+        assert_eq!(line, 2);
+        assert_eq!(message, "fov must be positive, found 0");
+
+        let (line, message) = parse_error("material glass dielectric 1.5 tint 0.5 0.5 0.5 0\n");
+        assert_eq!(line, 1);
+        assert_eq!(message, "tint distance must be positive, found 0");
+    }
+
+    #[test]
+    fn tint_with_the_wrong_number_of_values() {
+        let (line, message) = parse_error("background 0 0 0\nmaterial glass dielectric 1.5 tint 0.5 0.5\n");
+        assert_eq!(line, 2);
+        assert_eq!(message, "tint expects a color (r g b) and optionally a distance, found 2 value(s)");
+    }
+
+    #[test]
+    fn valid_scene() {
+        let source = "
+            # a lit box on a plane
+            image_width 40
+            samples_per_pixel 4
+            material white lambertian 0.7 0.7 0.7
+            material glass dielectric bk7 tint 0.8 0.9 1 2
+            material lamp diffuse_light 4 4 4
+            plane 0 0 0 0 1 0 white
+            box 0 0 0 1 1 1 white
+            sphere 0 2 0 0.5 glass
+            quad -1 3 -1 2 0 0 0 0 2 lamp
+            object ball sphere 0 0 0 1 lamp
+            instance ball translate 3 1 0
+            instance ball translate -3 1 0
+        ";
+        let scene = match parse_scene(source) {
+            Ok(scene) => scene,
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(scene.world().len(), 6);
+        assert_eq!(scene.lights().len(), 3);
+    }
+}
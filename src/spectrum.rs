@@ -0,0 +1,159 @@
+use std::sync::OnceLock;
+
+use crate::vec3::Vec3;
+
+// wavelengths are in nanometers, paths are only traced inside the range the eye sees
+pub const WAVELENGTH_MIN: f64 = 360.0;
+pub const WAVELENGTH_MAX: f64 = 830.0;
+
+// where the index of refraction of dispersive glass is quoted, the yellow helium d line
+pub const WAVELENGTH_D_LINE: f64 = 587.56;
+
+// a wavelength for the uniform sample u, with odds roughly following how bright each
+// wavelength looks, from pbrt's SampleVisibleWavelengths
+pub fn sample_wavelength(u: f64) -> f64 {
+    538.0 - 138.888889 * f64::atanh(0.85691062 - 1.82750197 * u)
+}
+
+// density of sample_wavelength per nanometer
+pub fn wavelength_pdf(wavelength: f64) -> f64 {
+    if !(WAVELENGTH_MIN..=WAVELENGTH_MAX).contains(&wavelength) {
+        return 0.0;
+    }
+    let c = f64::cosh(0.0072 * (wavelength - 538.0));
+    return 0.0039398042 / (c * c);
+}
+
+// a gaussian with a different width on either side of its peak
+fn piecewise_gaussian(x: f64, mu: f64, sigma_below: f64, sigma_above: f64) -> f64 {
+    let t = (x - mu) / if x < mu { sigma_below } else { sigma_above };
+    f64::exp(-0.5 * t * t)
+}
+
+// the lobes of the fit of the CIE 1931 standard observer by Wyman, Sloan and Shirley,
+// "Simple Analytic Approximations to the CIE XYZ Color Matching Functions", as
+// (weight, peak, width below, width above)
+const CIE_X: [(f64, f64, f64, f64); 3] = [(1.056, 599.8, 37.9, 31.0), (0.362, 442.0, 16.0, 26.7), (-0.065, 501.1, 20.4, 26.2)];
+const CIE_Y: [(f64, f64, f64, f64); 2] = [(0.821, 568.8, 46.9, 40.5), (0.286, 530.9, 16.3, 31.1)];
+const CIE_Z: [(f64, f64, f64, f64); 2] = [(1.217, 437.0, 11.8, 36.0), (0.681, 459.0, 26.0, 13.8)];
+
+fn color_matching(lobes: &[(f64, f64, f64, f64)], wavelength: f64) -> f64 {
+    lobes.iter().map(|&(weight, mu, below, above)| weight * piecewise_gaussian(wavelength, mu, below, above)).sum()
+}
+
+// the area under the color matching function
+fn color_matching_integral(lobes: &[(f64, f64, f64, f64)]) -> f64 {
+    let half_gaussian = f64::sqrt(std::f64::consts::PI / 2.0);
+    lobes.iter().map(|&(weight, _, below, above)| weight * half_gaussian * (below + above)).sum()
+}
+
+// the CIE XYZ response to light of a single wavelength
+pub fn cie_xyz(wavelength: f64) -> Vec3 {
+    Vec3::vec3(color_matching(&CIE_X, wavelength), color_matching(&CIE_Y, wavelength), color_matching(&CIE_Z, wavelength))
+}
+
+// linear sRGB from CIE XYZ
+pub fn xyz_to_rgb(xyz: &Vec3) -> Vec3 {
+    Vec3::vec3(
+        3.2404542 * xyz.x() - 1.5371385 * xyz.y() - 0.4985314 * xyz.z(),
+        -0.9692660 * xyz.x() + 1.8760108 * xyz.y() + 0.0415560 * xyz.z(),
+        0.0556434 * xyz.x() - 0.2040259 * xyz.y() + 1.0572252 * xyz.z(),
+    )
+}
+
+// linear sRGB of a spectrum that is one everywhere, computed once
+fn white_balance() -> Vec3 {
+    static WHITE: OnceLock<Vec3> = OnceLock::new();
+    *WHITE.get_or_init(|| xyz_to_rgb(&Vec3::vec3(color_matching_integral(&CIE_X), color_matching_integral(&CIE_Y), color_matching_integral(&CIE_Z))))
+}
+
+// the color of one sample of radiance at a wavelength drawn by sample_wavelength. it is
+// balanced so a spectrum that is one everywhere averages to white, like (1 1 1) in RGB
+pub fn wavelength_to_rgb(radiance: f64, wavelength: f64) -> Vec3 {
+    let pdf = wavelength_pdf(wavelength);
+    if pdf <= 0.0 {
+        return Vec3::vec3(0.0, 0.0, 0.0);
+    }
+    let white = white_balance();
+    let rgb = xyz_to_rgb(&(cie_xyz(wavelength) * (radiance / pdf)));
+    return Vec3::vec3(rgb.x() / white.x(), rgb.y() / white.y(), rgb.z() / white.z());
+}
+
+// Smits' "An RGB to Spectrum Conversion for Reflectances", the spectra of white, the
+// secondary and the primary colors, in 10 bins from 380 to 720 nm
+const SMITS_WHITE: [f64; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SMITS_CYAN: [f64; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SMITS_MAGENTA: [f64; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SMITS_YELLOW: [f64; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED: [f64; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f64; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SMITS_BLUE: [f64; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+// one of the Smits spectra, interpolated between the centers of its bins
+fn smits(spectrum: &[f64; 10], wavelength: f64) -> f64 {
+    let x = ((wavelength - 380.0) / 34.0 - 0.5).clamp(0.0, 9.0);
+    let i = usize::min(x as usize, 8);
+    let t = x - i as f64;
+    return (1.0 - t) * spectrum[i] + t * spectrum[i + 1];
+}
+
+// the value at wavelength of a smooth spectrum that looks like the linear color rgb: the
+// smallest channel as white, the gap to the middle one as a secondary color and the rest
+// as a primary
+pub fn rgb_to_spectrum(rgb: &Vec3, wavelength: f64) -> f64 {
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+    let at = |spectrum: &[f64; 10]| smits(spectrum, wavelength);
+
+    if r <= g && r <= b {
+        let base = r * at(&SMITS_WHITE);
+        if g <= b {
+            base + (g - r) * at(&SMITS_CYAN) + (b - g) * at(&SMITS_BLUE)
+        } else {
+            base + (b - r) * at(&SMITS_CYAN) + (g - b) * at(&SMITS_GREEN)
+        }
+    } else if g <= r && g <= b {
+        let base = g * at(&SMITS_WHITE);
+        if r <= b {
+            base + (r - g) * at(&SMITS_MAGENTA) + (b - r) * at(&SMITS_BLUE)
+        } else {
+            base + (b - g) * at(&SMITS_MAGENTA) + (r - b) * at(&SMITS_RED)
+        }
+    } else {
+        let base = b * at(&SMITS_WHITE);
+        if r <= g {
+            base + (r - b) * at(&SMITS_YELLOW) + (g - r) * at(&SMITS_GREEN)
+        } else {
+            base + (g - b) * at(&SMITS_YELLOW) + (r - g) * at(&SMITS_RED)
+        }
+    }
+}
+
+// a color of the RGB parts of the renderer as seen by a path that carries a wavelength,
+// the same in every channel. colors pass through unchanged without one
+pub fn at_wavelength(rgb: &Vec3, wavelength: Option<f64>) -> Vec3 {
+    match wavelength {
+        Some(wavelength) => {
+            let value = rgb_to_spectrum(rgb, wavelength);
+            Vec3::vec3(value, value, value)
+        }
+        None => *rgb,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_spectrum_is_white() {
+        let n = 100000;
+        let mut sum = Vec3::vec3(0.0, 0.0, 0.0);
+        for i in 0..n {
+            sum += wavelength_to_rgb(1.0, sample_wavelength((i as f64 + 0.5) / n as f64));
+        }
+        let average = sum / n as f64;
+        for channel in [average.x(), average.y(), average.z()] {
+            assert!((channel - 1.0).abs() < 1e-2, "{:?}", average);
+        }
+    }
+}
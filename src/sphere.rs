@@ -1,53 +1,95 @@
+use std::sync::Arc;
 use crate::ray::Ray;
 use crate::vec3::{
     Vec3,
-    dot,};
+    cross,
+    dot,
+    unit_vector,};
 use crate::hittable::{HitRecord, Hittable};
 use crate::interval::Interval;
 use crate::material::*;
+use crate::aabb::AABB;
+use crate::onb::Onb;
+use crate::utility::Rng;
+use crate::vec3::{random_to_sphere, random_unit_vector};
 
 pub struct Sphere {
     center1: Vec3,
     radius: f64,
-    mat: Materials,
+    mat: Arc<dyn Material>,
     is_moving: bool,
     center_vec: Vec3,
+    bbox: AABB,
 }
 
 impl Sphere {
-    pub fn sphere(_center: Vec3, _radius: f64, _material: Materials) -> Sphere {
+    pub fn sphere(_center: Vec3, _radius: f64, _material: Arc<dyn Material>) -> Sphere {
+        let rvec = Vec3::vec3(_radius, _radius, _radius);
         Sphere {
             center1: _center,
             radius: _radius,
             mat: _material,
             is_moving: false,
             center_vec: _center,
+            bbox: AABB::aabb(&(_center - rvec), &(_center + rvec)),
         }
     }
 
-    pub fn sphere_moving(_center1: Vec3, _center2: Vec3, _radius: f64, _material: Materials) -> Sphere {
+    pub fn sphere_moving(_center1: Vec3, _center2: Vec3, _radius: f64, _material: Arc<dyn Material>) -> Sphere {
+        // the box has to enclose the sphere over the whole shutter interval
+        let rvec = Vec3::vec3(_radius, _radius, _radius);
+        let box1 = AABB::aabb(&(_center1 - rvec), &(_center1 + rvec));
+        let box2 = AABB::aabb(&(_center2 - rvec), &(_center2 + rvec));
         Sphere {
             center1: _center1,
             radius: _radius,
             mat: _material,
             is_moving: true,
             center_vec: _center2 - _center1,  
+            bbox: AABB::aabb_boxes(&box1, &box2),
         }
     }
 
     pub fn center(&self, time: f64) -> Vec3 {
         self.center1 + time * self.center_vec
     }
+
+    // p: a given point on the sphere of radius one, centered at the origin
+    // u: returned value [0,1] of angle around the Y axis from X=-1
+    // v: returned value [0,1] of angle from Y=-1 to Y=+1
+    pub fn get_sphere_uv(p: &Vec3) -> (f64, f64) {
+        let theta = f64::acos(-p.y());
+        let phi = f64::atan2(-p.z(), p.x()) + std::f64::consts::PI;
+
+        return (phi / (2.0 * std::f64::consts::PI), theta / std::f64::consts::PI);
+    }
+
+    // derivatives of the point on the sphere with respect to the (u, v) of get_sphere_uv,
+    // with p on the unit sphere as above
+    pub fn get_sphere_tangents(p: &Vec3, radius: f64) -> (Vec3, Vec3) {
+        let pi = std::f64::consts::PI;
+        let sin_theta = f64::sqrt(f64::max(0.0, 1.0 - p.y() * p.y()));
+        let dpdu = 2.0 * pi * radius * Vec3::vec3(p.z(), 0.0, -p.x());
+
+        // the parameterization is degenerate at the poles, any tangent frame will do there
+        if sin_theta < 1e-8 {
+            let dpdu = Vec3::vec3(2.0 * pi * radius, 0.0, 0.0);
+            let dpdv = pi * radius * unit_vector(cross(p, &dpdu));
+            return (dpdu, dpdv);
+        }
+
+        let dpdv = pi * radius * Vec3::vec3(-p.x() * p.y() / sin_theta, sin_theta, -p.y() * p.z() / sin_theta);
+        return (dpdu, dpdv);
+    }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
-        let center: Vec3;
-        if self.is_moving {
-            center = self.center(r.time()); 
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let center = if self.is_moving {
+            self.center(r.time())
         } else {
-            center = self.center1;
-        }
+            self.center1
+        };
         let oc = r.origin() - center;
         let a = r.direction().length_squared();
         let half_b = dot(&oc, &r.direction());
@@ -69,9 +111,45 @@ impl Hittable for Sphere {
         rec.set_t(&root);
         rec.set_p(&r.at(&rec.t()));
         let outward_normal = (rec.p() - center) / self.radius;
-        rec.set_face_normal(&r, &outward_normal);
+        rec.set_face_normal(r, &outward_normal);
+        let (u, v) = Sphere::get_sphere_uv(&outward_normal);
+        let (dpdu, dpdv) = Sphere::get_sphere_tangents(&outward_normal, self.radius);
+        rec.set_uv(&u, &v);
+        rec.set_tangents(&dpdu, &dpdv);
         rec.set_mat(&self.mat);
 
         return true;
     }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+
+    // sampled uniformly over the cone of directions it covers, moving spheres are
+    // sampled at their starting position
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        if !self.hit(&Ray::ray(origin, direction), Interval::interval(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = (self.center1 - *origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return 1.0 / (4.0 * std::f64::consts::PI);
+        }
+        let cos_theta_max = f64::sqrt(1.0 - self.radius * self.radius / distance_squared);
+        let solid_angle = 2.0 * std::f64::consts::PI * (1.0 - cos_theta_max);
+
+        return 1.0 / solid_angle;
+    }
+
+    fn random(&self, origin: &Vec3, rng: &mut Rng) -> Vec3 {
+        let direction = self.center1 - *origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return random_unit_vector(rng);
+        }
+        let uvw = Onb::onb(&direction);
+        return uvw.transform(&random_to_sphere(self.radius, distance_squared, rng));
+    }
 }
@@ -0,0 +1,165 @@
+use std::path::Path;
+use std::sync::Arc;
+use crate::vec3::Vec3;
+use crate::framebuffer::Framebuffer;
+use crate::input::load_image;
+use crate::interval::Interval;
+use crate::perlin::Perlin;
+use crate::utility::Rng;
+
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3;
+}
+
+#[derive(Copy, Clone)]
+pub struct SolidColor {
+    albedo: Vec3,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Vec3) -> SolidColor {
+        Self {
+            albedo,
+        }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Vec3) -> Vec3 {
+        self.albedo
+    }
+}
+
+// alternates between two textures on a 3d grid of cubes
+#[derive(Clone)]
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<Textures>,
+    odd: Arc<Textures>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Textures, odd: Textures) -> CheckerTexture {
+        Self {
+            inv_scale: 1.0 / scale,
+            even: Arc::new(even),
+            odd: Arc::new(odd),
+        }
+    }
+
+    pub fn from_colors(scale: f64, c1: Vec3, c2: Vec3) -> CheckerTexture {
+        CheckerTexture::new(scale, Textures::SOLID_COLOR(SolidColor::new(c1)), Textures::SOLID_COLOR(SolidColor::new(c2)))
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let x_integer = (self.inv_scale * p.x()).floor() as i64;
+        let y_integer = (self.inv_scale * p.y()).floor() as i64;
+        let z_integer = (self.inv_scale * p.z()).floor() as i64;
+
+        if (x_integer + y_integer + z_integer) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ImageTexture {
+    image: Arc<Framebuffer>,
+}
+
+impl ImageTexture {
+    pub fn new(image: Framebuffer) -> ImageTexture {
+        Self {
+            image: Arc::new(image),
+        }
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<ImageTexture> {
+        Ok(ImageTexture::new(load_image(path)?))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Vec3) -> Vec3 {
+        // solid cyan as a debugging aid when there is no texture data
+        if self.image.height() == 0 || self.image.width() == 0 {
+            return Vec3::vec3(0.0, 1.0, 1.0);
+        }
+
+        // clamp input texture coordinates to [0,1] x [1,0], images are stored top row first
+        let u = Interval::interval(0.0, 1.0).clamp(u);
+        let v = 1.0 - Interval::interval(0.0, 1.0).clamp(v);
+
+        let i = usize::min((u * self.image.width() as f64) as usize, self.image.width() - 1);
+        let j = usize::min((v * self.image.height() as f64) as usize, self.image.height() - 1);
+        return self.image.pixel(i, j);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NoiseKind {
+    // smooth perlin noise
+    Noise,
+    // several octaves of noise summed together
+    Turbulence,
+    // turbulence used to phase shift a sine wave along z
+    Marble,
+}
+
+#[derive(Clone)]
+pub struct NoiseTexture {
+    noise: Arc<Perlin>,
+    scale: f64,
+    kind: NoiseKind,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64, kind: NoiseKind, rng: &mut Rng) -> NoiseTexture {
+        Self {
+            noise: Arc::new(Perlin::perlin(rng)),
+            scale,
+            kind,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Vec3 {
+        let gray = match self.kind {
+            NoiseKind::Noise => 0.5 * (1.0 + self.noise.noise(&(self.scale * *p))),
+            NoiseKind::Turbulence => self.noise.turb(&(self.scale * *p), 7),
+            NoiseKind::Marble => 0.5 * (1.0 + f64::sin(self.scale * p.z() + 10.0 * self.noise.turb(p, 7))),
+        };
+        return Vec3::vec3(gray, gray, gray);
+    }
+}
+
+#[derive(Clone)]
+#[allow(non_camel_case_types)]
+pub enum Textures {
+    SOLID_COLOR(SolidColor),
+    CHECKER(CheckerTexture),
+    IMAGE(ImageTexture),
+    NOISE(NoiseTexture),
+}
+
+impl Texture for Textures {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        match self {
+            Textures::SOLID_COLOR(s) => s.value(u, v, p),
+            Textures::CHECKER(c) => c.value(u, v, p),
+            Textures::IMAGE(i) => i.value(u, v, p),
+            Textures::NOISE(n) => n.value(u, v, p),
+        }
+    }
+}
+
+impl From<Vec3> for Textures {
+    fn from(albedo: Vec3) -> Textures {
+        Textures::SOLID_COLOR(SolidColor::new(albedo))
+    }
+}
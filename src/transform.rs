@@ -0,0 +1,373 @@
+use std::ops::Mul;
+use std::sync::Arc;
+use crate::ray::Ray;
+use crate::vec3::*;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::aabb::AABB;
+use crate::utility::Rng;
+
+// row major affine matrix, points are column vectors with an implicit w of one
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn mat4(m: [[f64; 4]; 4]) -> Mat4 {
+        Mat4 { m }
+    }
+
+    pub fn identity() -> Mat4 {
+        Mat4::scaling(&Vec3::vec3(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: &Vec3) -> Mat4 {
+        Mat4::mat4([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: &Vec3) -> Mat4 {
+        Mat4::mat4([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_x(degrees: f64) -> Mat4 {
+        Mat4::rotation(&Vec3::vec3(1.0, 0.0, 0.0), degrees)
+    }
+
+    pub fn rotation_y(degrees: f64) -> Mat4 {
+        Mat4::rotation(&Vec3::vec3(0.0, 1.0, 0.0), degrees)
+    }
+
+    pub fn rotation_z(degrees: f64) -> Mat4 {
+        Mat4::rotation(&Vec3::vec3(0.0, 0.0, 1.0), degrees)
+    }
+
+    // counter clockwise rotation around the axis when looking down on it
+    pub fn rotation(axis: &Vec3, degrees: f64) -> Mat4 {
+        let a = unit_vector(*axis);
+        let (sin_theta, cos_theta) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos_theta;
+        Mat4::mat4([
+            [t * a.x() * a.x() + cos_theta, t * a.x() * a.y() - sin_theta * a.z(), t * a.x() * a.z() + sin_theta * a.y(), 0.0],
+            [t * a.x() * a.y() + sin_theta * a.z(), t * a.y() * a.y() + cos_theta, t * a.y() * a.z() - sin_theta * a.x(), 0.0],
+            [t * a.x() * a.z() - sin_theta * a.y(), t * a.y() * a.z() + sin_theta * a.x(), t * a.z() * a.z() + cos_theta, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Mat4 { m }
+    }
+
+    // gauss jordan elimination with partial pivoting, None for singular matrices
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Mat4::identity().m;
+
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs())).unwrap_or(col);
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let factor = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+
+        Some(Mat4 { m: inv })
+    }
+
+    // determinant of the linear part, how much volumes are scaled
+    pub fn linear_determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::vec3(
+            m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3],
+            m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3],
+            m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3],
+        )
+    }
+
+    // directions ignore the translation
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::vec3(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    // the right hand side is applied first
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+// moves an object by a fixed offset
+pub struct Translate {
+    object: Arc<dyn Hittable>,
+    offset: Vec3,
+    bbox: AABB,
+}
+
+impl Translate {
+    pub fn translate(object: Arc<dyn Hittable>, offset: Vec3) -> Translate {
+        let bbox = transform_box(&object.bounding_box(), |p| *p + offset);
+        Translate {
+            object,
+            offset,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Translate {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        // move the ray backwards by the offset
+        let mut offset_r = *r;
+        offset_r.set_origin(&(r.origin() - self.offset));
+
+        // determine whether an intersection exists along the offset ray (and if so, where)
+        if !self.object.hit(&offset_r, ray_t, rec) {
+            return false;
+        }
+
+        // move the intersection point forwards by the offset
+        rec.set_p(&(rec.p() + self.offset));
+
+        return true;
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        self.object.pdf_value(&(*origin - self.offset), direction)
+    }
+
+    fn random(&self, origin: &Vec3, rng: &mut Rng) -> Vec3 {
+        self.object.random(&(*origin - self.offset), rng)
+    }
+}
+
+// rotates an object around one of the coordinate axes (0 = x, 1 = y, 2 = z)
+pub struct Rotate<const AXIS: usize> {
+    object: Arc<dyn Hittable>,
+    sin_theta: f64,
+    cos_theta: f64,
+    bbox: AABB,
+}
+
+pub type RotateX = Rotate<0>;
+pub type RotateY = Rotate<1>;
+pub type RotateZ = Rotate<2>;
+
+impl<const AXIS: usize> Rotate<AXIS> {
+    // the angle is in degrees, counter clockwise when looking down the axis
+    pub fn rotate(object: Arc<dyn Hittable>, angle: f64) -> Rotate<AXIS> {
+        let (sin_theta, cos_theta) = angle.to_radians().sin_cos();
+        let bbox = transform_box(&object.bounding_box(), |p| rotate_around::<AXIS>(p, sin_theta, cos_theta));
+        Rotate {
+            object,
+            sin_theta,
+            cos_theta,
+            bbox,
+        }
+    }
+
+    fn to_object(&self, v: &Vec3) -> Vec3 {
+        rotate_around::<AXIS>(v, -self.sin_theta, self.cos_theta)
+    }
+
+    fn to_world(&self, v: &Vec3) -> Vec3 {
+        rotate_around::<AXIS>(v, self.sin_theta, self.cos_theta)
+    }
+}
+
+impl<const AXIS: usize> Hittable for Rotate<AXIS> {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        // transform the ray from world space to object space
+        let mut rotated_r = *r;
+        rotated_r.set_origin(&self.to_object(&r.origin()));
+        rotated_r.set_direction(&self.to_object(&r.direction()));
+
+        // determine whether an intersection exists in object space (and if so, where)
+        if !self.object.hit(&rotated_r, ray_t, rec) {
+            return false;
+        }
+
+        // transform the intersection from object space back to world space
+        rec.set_p(&self.to_world(&rec.p()));
+        rec.set_normal(&self.to_world(&rec.normal()));
+        rec.set_tangents(&self.to_world(&rec.dpdu()), &self.to_world(&rec.dpdv()));
+
+        return true;
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+
+    // rotations preserve solid angles, so the density carries over unchanged
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        self.object.pdf_value(&self.to_object(origin), &self.to_object(direction))
+    }
+
+    fn random(&self, origin: &Vec3, rng: &mut Rng) -> Vec3 {
+        self.to_world(&self.object.random(&self.to_object(origin), rng))
+    }
+}
+
+// rotation in the plane of the two axes following AXIS, in cyclic order
+fn rotate_around<const AXIS: usize>(v: &Vec3, sin_theta: f64, cos_theta: f64) -> Vec3 {
+    let i = (AXIS + 1) % 3;
+    let j = (AXIS + 2) % 3;
+    let mut c = [v.x(), v.y(), v.z()];
+    let (a, b) = (c[i], c[j]);
+    c[i] = cos_theta * a - sin_theta * b;
+    c[j] = sin_theta * a + cos_theta * b;
+    Vec3::vec3(c[0], c[1], c[2])
+}
+
+// any invertible affine transform. rays go into object space through the inverse, normals
+// come back through its transpose so they stay perpendicular under non uniform scaling
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    to_world: Mat4,
+    to_object: Mat4,
+    normal_to_world: Mat4,
+    determinant: f64,
+    bbox: AABB,
+}
+
+impl Transform {
+    // returns None if the matrix cannot be inverted
+    pub fn transform(object: Arc<dyn Hittable>, to_world: &Mat4) -> Option<Transform> {
+        let to_object = to_world.inverse()?;
+        let bbox = transform_box(&object.bounding_box(), |p| to_world.transform_point(p));
+        Some(Transform {
+            object,
+            to_world: *to_world,
+            to_object,
+            normal_to_world: to_object.transpose(),
+            determinant: to_world.linear_determinant().abs(),
+            bbox,
+        })
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.to_world
+    }
+}
+
+impl Hittable for Transform {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        // the direction is not normalized, so t is the same in both spaces
+        let origin = self.to_object.transform_point(&r.origin());
+        let direction = self.to_object.transform_vector(&r.direction());
+        let mut object_r = *r;
+        object_r.set_origin(&origin);
+        object_r.set_direction(&direction);
+
+        if !self.object.hit(&object_r, ray_t, rec) {
+            return false;
+        }
+
+        rec.set_p(&self.to_world.transform_point(&rec.p()));
+        rec.set_normal(&unit_vector(self.normal_to_world.transform_vector(&rec.normal())));
+        rec.set_tangents(&self.to_world.transform_vector(&rec.dpdu()), &self.to_world.transform_vector(&rec.dpdv()));
+
+        return true;
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+
+    // the linear part maps a unit direction w to A w, which stretches solid angle by
+    // |det A| / |A w|^3, the density shrinks by the same factor
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let object_direction = self.to_object.transform_vector(direction);
+        let object_pdf = self.object.pdf_value(&self.to_object.transform_point(origin), &object_direction);
+        if object_pdf == 0.0 {
+            return 0.0;
+        }
+        let stretch = self.to_world.transform_vector(&unit_vector(object_direction)).length();
+        return object_pdf * stretch * stretch * stretch / self.determinant;
+    }
+
+    fn random(&self, origin: &Vec3, rng: &mut Rng) -> Vec3 {
+        let object_direction = self.object.random(&self.to_object.transform_point(origin), rng);
+        self.to_world.transform_vector(&object_direction)
+    }
+}
+
+// bounds of the eight transformed corners. empty boxes stay empty, and unbounded objects
+// stay unbounded since their infinite corners cannot be transformed
+fn transform_box(bbox: &AABB, f: impl Fn(&Vec3) -> Vec3) -> AABB {
+    let axes = [bbox.axis(0), bbox.axis(1), bbox.axis(2)];
+    if axes.iter().any(|a| a.min() > a.max()) {
+        return *bbox;
+    }
+    if axes.iter().any(|a| !a.min().is_finite() || !a.max().is_finite()) {
+        return AABB::universe();
+    }
+
+    let mut result = AABB::identity();
+    for corner in 0..8 {
+        let pick = |axis: usize| if corner & (1 << axis) != 0 { axes[axis].max() } else { axes[axis].min() };
+        let p = f(&Vec3::vec3(pick(0), pick(1), pick(2)));
+        result = AABB::aabb_boxes(&result, &AABB::aabb(&p, &p));
+    }
+    return result;
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+use crate::ray::Ray;
+use crate::vec3::*;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::*;
+use crate::aabb::AABB;
+use crate::utility::*;
+
+// triangle with corners q, q + u and q + v, the same plane setup as Quad
+pub struct Triangle {
+    q: Vec3,
+    u: Vec3,
+    v: Vec3,
+    mat: Arc<dyn Material>,
+    bbox: AABB,
+    normal: Vec3,
+    d: f64,
+    w: Vec3,
+    area: f64,
+}
+
+impl Triangle {
+    pub fn triangle(a: Vec3, b: Vec3, c: Vec3, _material: Arc<dyn Material>) -> Triangle {
+        let u = b - a;
+        let v = c - a;
+        let n = cross(&u, &v);
+        let normal = unit_vector(n);
+
+        let bbox = AABB::aabb_boxes(&AABB::aabb(&a, &b), &AABB::aabb(&c, &c));
+
+        Triangle {
+            q: a,
+            u,
+            v,
+            mat: _material,
+            bbox: bbox.pad_to_minimums(),
+            normal,
+            d: dot(&normal, &a),
+            w: n / dot(&n, &n),
+            area: 0.5 * n.length(),
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let denom = dot(&self.normal, &r.direction());
+
+        // no hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = (self.d - dot(&self.normal, &r.origin())) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        // alpha and beta are the barycentric weights of the corners q + u and q + v
+        let intersection = r.at(&t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = dot(&self.w, &cross(&planar_hitpt_vector, &self.v));
+        let beta = dot(&self.w, &cross(&self.u, &planar_hitpt_vector));
+
+        if alpha < 0.0 || beta < 0.0 || alpha + beta > 1.0 {
+            return false;
+        }
+
+        rec.set_t(&t);
+        rec.set_p(&intersection);
+        rec.set_mat(&self.mat);
+        rec.set_face_normal(r, &self.normal);
+        rec.set_uv(&alpha, &beta);
+        rec.set_tangents(&self.u, &self.v);
+
+        return true;
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        if !self.hit(&Ray::ray(origin, direction), Interval::interval(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t() * rec.t() * direction.length_squared();
+        let cosine = f64::abs(dot(direction, &rec.normal()) / direction.length());
+
+        return distance_squared / (cosine * self.area);
+    }
+
+    // uniform over the triangle, folding the samples of the parallelogram back into it
+    fn random(&self, origin: &Vec3, rng: &mut Rng) -> Vec3 {
+        let mut alpha = random_double(rng);
+        let mut beta = random_double(rng);
+        if alpha + beta > 1.0 {
+            alpha = 1.0 - alpha;
+            beta = 1.0 - beta;
+        }
+        let p = self.q + (alpha * self.u) + (beta * self.v);
+        return p - *origin;
+    }
+}
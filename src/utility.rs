@@ -1,11 +1,19 @@
-use fastrand::*;
+pub use fastrand::Rng;
 
-pub fn random_double() -> f64{
+pub fn random_double(rng: &mut Rng) -> f64 {
     // return a real in 0, 1
-    fastrand::f64()
+    rng.f64()
 }
 
-pub fn random_double_range(min: f64, max: f64) -> f64 {
+pub fn random_double_range(min: f64, max: f64, rng: &mut Rng) -> f64 {
     // return a real in [min, max]
-    min + (max - min) * random_double()
+    min + (max - min) * random_double(rng)
+}
+
+// splitmix64 finalizer, turns a base seed and a stream index into an independent seed
+pub fn mix_seed(seed: u64, stream: u64) -> u64 {
+    let mut z = seed ^ stream.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    return z ^ (z >> 31);
 }
@@ -7,6 +7,7 @@ use std::ops::{
     DivAssign,
     MulAssign,
     Neg,
+    Index,
 };
 use crate::utility::*;
 
@@ -66,12 +67,12 @@ impl Vec3 {
         self.length_squared().sqrt()
     }
 
-    pub fn random() -> Vec3 {
-        Vec3::vec3(random_double(), random_double(), random_double())
+    pub fn random(rng: &mut Rng) -> Vec3 {
+        Vec3::vec3(random_double(rng), random_double(rng), random_double(rng))
     }
     
-    pub fn random_range(min: f64, max: f64) -> Vec3 {
-        Vec3::vec3(random_double_range(min, max), random_double_range(min, max),random_double_range(min, max)) 
+    pub fn random_range(min: f64, max: f64, rng: &mut Rng) -> Vec3 {
+        Vec3::vec3(random_double_range(min, max, rng), random_double_range(min, max, rng),random_double_range(min, max, rng)) 
     }
 
     pub fn near_zero(&self) -> bool {
@@ -79,6 +80,18 @@ impl Vec3 {
         (self.e[0].abs() < s) && (self.e[1].abs() < s) && (self.e[2].abs() < s)
     }
 
+    pub fn max_component(&self) -> f64 {
+        f64::max(self.e[0], f64::max(self.e[1], self.e[2]))
+    }
+
+}
+
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, i: usize) -> &f64 {
+        &self.e[i]
+    }
 }
 
 impl Add for Vec3 {
@@ -215,30 +228,48 @@ pub fn unit_vector(v: Vec3) -> Vec3 {
     v / v.length()
 }
 
-pub fn random_in_unit_sphere() -> Vec3 {
+pub fn random_in_unit_sphere(rng: &mut Rng) -> Vec3 {
     loop {
-        let p = Vec3::random_range(-1.0, 1.0);
+        let p = Vec3::random_range(-1.0, 1.0, rng);
         if p.length_squared() < 1.0 {
             return p;
         }
     }
 }
 
-pub fn random_unit_vector() -> Vec3 {
-    unit_vector(random_in_unit_sphere())
+pub fn random_unit_vector(rng: &mut Rng) -> Vec3 {
+    unit_vector(random_in_unit_sphere(rng))
 }
 
-pub fn random_in_unit_disk() -> Vec3 {
+pub fn random_in_unit_disk(rng: &mut Rng) -> Vec3 {
     loop {
-        let p = Vec3::vec3(random_double_range(-1.0, 1.0), random_double_range(-1.0, 1.0), 0.0);
+        let p = Vec3::vec3(random_double_range(-1.0, 1.0, rng), random_double_range(-1.0, 1.0, rng), 0.0);
         if p.length_squared() < 1.0 {
             return p;
         }
     }
 }
 
-pub fn random_on_hemisphere(normal: &Vec3) -> Vec3 {
-    let on_unit_sphere = random_unit_vector();
+// maps the unit square onto the unit disk keeping areas, and neighboring samples close,
+// Shirley and Chiu's concentric mapping
+pub fn sample_unit_disk(u: f64, v: f64) -> Vec3 {
+    let a = 2.0 * u - 1.0;
+    let b = 2.0 * v - 1.0;
+    if a == 0.0 && b == 0.0 {
+        return Vec3::vec3(0.0, 0.0, 0.0);
+    }
+
+    let quarter_pi = std::f64::consts::FRAC_PI_4;
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, quarter_pi * (b / a))
+    } else {
+        (b, 2.0 * quarter_pi - quarter_pi * (a / b))
+    };
+    return Vec3::vec3(r * theta.cos(), r * theta.sin(), 0.0);
+}
+
+pub fn random_on_hemisphere(normal: &Vec3, rng: &mut Rng) -> Vec3 {
+    let on_unit_sphere = random_unit_vector(rng);
     if dot(&on_unit_sphere, normal) > 0.0 {
         return on_unit_sphere;
     } else {
@@ -246,8 +277,34 @@ pub fn random_on_hemisphere(normal: &Vec3) -> Vec3 {
     }
 }
 
+// cosine weighted direction around +z
+pub fn random_cosine_direction(rng: &mut Rng) -> Vec3 {
+    let r1 = random_double(rng);
+    let r2 = random_double(rng);
+
+    let phi = 2.0 * std::f64::consts::PI * r1;
+    let x = f64::cos(phi) * f64::sqrt(r2);
+    let y = f64::sin(phi) * f64::sqrt(r2);
+    let z = f64::sqrt(1.0 - r2);
+
+    return Vec3::vec3(x, y, z);
+}
+
+// uniform direction towards a sphere of the given radius at distance_squared, around +z
+pub fn random_to_sphere(radius: f64, distance_squared: f64, rng: &mut Rng) -> Vec3 {
+    let r1 = random_double(rng);
+    let r2 = random_double(rng);
+    let z = 1.0 + r2 * (f64::sqrt(1.0 - radius * radius / distance_squared) - 1.0);
+
+    let phi = 2.0 * std::f64::consts::PI * r1;
+    let x = f64::cos(phi) * f64::sqrt(1.0 - z * z);
+    let y = f64::sin(phi) * f64::sqrt(1.0 - z * z);
+
+    return Vec3::vec3(x, y, z);
+}
+
 pub fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
-    *v - 2.0 * dot(&v, &n) * *n 
+    *v - 2.0 * dot(v, n) * *n 
 }
 
 pub fn refract(uv: Vec3, n: Vec3, etai_over_etat: f64) -> Vec3 {